@@ -1,7 +1,13 @@
 use crate::*;
-use minecraft_protocol::{components::blocks::MultiBlockChange, components::chat::ChatMode, components::slots::MainHand, MinecraftPacketPart};
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use crate::network::{ConnectionEvent, ConnectionState, NetworkStats, PacketSender, PluginChannels, ReconnectPolicy};
+use minecraft_protocol::{
+    components::blocks::{BlockFace, DiggingState, MultiBlockChange},
+    components::chat::ChatMode,
+    packets::RawBytes,
+    MinecraftPacketPart,
+};
+use std::sync::{mpsc::Receiver, Arc, Mutex};
+use std::time::{Duration, Instant};
 
 #[derive(Debug)]
 pub struct PlayerPosition {
@@ -13,107 +19,222 @@ pub struct PlayerPosition {
 }
 
 pub struct Bot {
-    pub username: String,
-    pub addr: String,
-    pub port: u16,
+    pub config: BotConfig,
+    pub connection_state: ConnectionState,
+    shutdown_requested: bool,
+    network_stats: Arc<Mutex<NetworkStats>>,
+    plugin_channels: Arc<PluginChannels>,
+    sender: PacketSender,
     pub map: Map,
     pub entities: Entities,
+    pub players: PlayerList,
     pub self_entity_id: Option<i32>,
+    /// The block the bot started digging, until it finishes or cancels
+    pub digging: Option<(i32, i32, i32)>,
     pub position: Option<PlayerPosition>,
     pub spawn_position: Option<Position>,
     pub world_name: Option<String>,
     pub windows: Windows,
+    pub events: EventBus,
+    /// Outgoing chat messages, kept across reconnections
+    pub chat: ChatQueue,
 
     pub health: f32,
     pub food: u32,
     pub food_saturation: f32,
-    pub vertical_speed: f64,
-    pub mission: Arc<Mutex<Option<Box<dyn Mission<Result<String, String>>>>>>,
+    pub time: WorldTime,
+    pub weather: Weather,
+    pub physics: Physics,
+    pub missions: Arc<Mutex<MissionScheduler>>,
+    /// Missions submitted through the bot, handed to the scheduler once it is unlocked
+    submitted_missions: Vec<ScheduledMission>,
 }
 
 impl Bot {
-    pub fn create(addr: String, port: u16, username: String) {
-        debug!("Connecting {} to {}:{}", username, addr, port);
-        let (receiver, sender) = crate::network::connect(&addr, port, &username);
-        info!("{} is connected on {}:{}", username, addr, port);
-        let sender2 = sender.clone();
-
-        let bot = Arc::new(Mutex::new(Bot {
-            username,
-            addr,
-            port,
+    pub(crate) fn new(config: BotConfig, sender: PacketSender) -> Bot {
+        Bot {
+            plugin_channels: Arc::clone(&config.connection_options.plugin_channels),
+            config,
+            connection_state: ConnectionState::Connected,
+            shutdown_requested: false,
+            network_stats: Arc::new(Mutex::new(NetworkStats::default())),
+            sender: sender.clone(),
             map: Map::new(),
             entities: Entities::new(sender.clone()),
+            players: PlayerList::new(),
             position: None,
             spawn_position: None,
             self_entity_id: None,
+            digging: None,
             world_name: None,
-            windows: Windows::new(sender.clone()),
-            mission: Arc::new(Mutex::new(None)),
+            windows: Windows::new(sender),
+            events: EventBus::new(),
+            chat: ChatQueue::new(),
+            missions: Arc::new(Mutex::new(MissionScheduler::new())),
+            submitted_missions: Vec::new(),
 
             health: 11.0,
             food: 11,
             food_saturation: 0.0,
-            vertical_speed: 0.0,
-        }));
-        let bot2 = Arc::clone(&bot);
+            time: WorldTime::default(),
+            weather: Weather::default(),
+            physics: Physics::new(),
+        }
+    }
 
-        // Wait for the server to be ready.
-        std::thread::sleep(std::time::Duration::from_millis(500));
+    /// Forgets everything about the previous session (world, entities, inventory, position...) before a new connection.
+    /// The mission and the connection settings are kept so that the bot can resume its work after joining.
+    fn reset_session(&mut self, sender: PacketSender, network_stats: Arc<Mutex<NetworkStats>>) {
+        self.connection_state = ConnectionState::Connected;
+        self.network_stats = network_stats;
+        self.sender = sender.clone();
+        self.map = Map::new();
+        self.entities = Entities::new(sender.clone());
+        self.players = PlayerList::new();
+        self.windows = Windows::new(sender);
+        self.position = None;
+        self.spawn_position = None;
+        self.self_entity_id = None;
+        self.digging = None;
+        self.world_name = None;
+        self.health = 11.0;
+        self.food = 11;
+        self.food_saturation = 0.0;
+        self.time = WorldTime::default();
+        self.weather = Weather::default();
+        self.physics.reset();
+    }
+
+    /// Forgets the blocks and entities of the previous world, after joining or respawning.
+    /// The position stays unknown until the server teleports the bot.
+    fn change_world(&mut self, world_name: &str, dimension: DimensionType) {
+        debug!("Entering {} (min_y {}, height {})", world_name, dimension.min_y, dimension.height);
+        self.map = Map::with_dimension(dimension);
+        self.entities = Entities::new(self.sender.clone());
+        if let Some(self_entity_id) = self.self_entity_id {
+            self.entities.add_self(self_entity_id);
+        }
+        self.world_name = Some(world_name.to_string());
+        self.position = None;
+        self.digging = None;
+        self.physics.reset();
+        self.weather = Weather::default();
+    }
 
-        sender
-            .send(
-                ServerboundPacket::ClientSettings {
-                    locale: "en_US",
-                    render_distance: 32,
-                    chat_mode: ChatMode::Enabled,
-                    chat_colors_enabled: true,
-                    displayed_skin_parts: 127,
-                    main_hand: MainHand::Right,
-                    disable_text_filtering: true,
+    /// Connects the bot and runs it on the calling thread until the connection is closed, reconnecting according to the config.
+    /// Returns the reason why the last session ended.
+    /// Use [BotBuilder] or [BotHandle::spawn] to keep control over the bot.
+    pub fn create(config: BotConfig) -> ConnectionState {
+        let (sender, _receiver) = PacketSender::channel();
+        let bot = Arc::new(Mutex::new(Bot::new(config, sender)));
+        Bot::run(&bot)
+    }
+
+    /// Runs the bot until it is shut down or the reconnection policy gives up.
+    pub(crate) fn run(bot: &Arc<Mutex<Bot>>) -> ConnectionState {
+        let BotConfig {
+            addr,
+            port,
+            username,
+            connection_options,
+            reconnect_policy,
+            ..
+        } = bot.lock().unwrap().config.clone();
+        let mut reconnection = Reconnection::new(reconnect_policy);
+
+        loop {
+            debug!("Connecting {} to {}:{}", username, addr, port);
+            let network_stats = Arc::new(Mutex::new(NetworkStats::default()));
+            let mut joined = false;
+            let state = match crate::network::connect(&addr, port, &username, &connection_options, Arc::clone(&network_stats)) {
+                Ok((receiver, sender)) => {
+                    info!("{} is connected on {}:{}", username, addr, port);
+                    if let Some(state) = Bot::start_session(bot, &sender, network_stats) {
+                        return state;
+                    }
+                    let state = Bot::run_session(bot, receiver, sender);
+                    joined = bot.lock().unwrap().self_entity_id.is_some();
+                    state
                 }
-                .serialize_minecraft_packet()
-                .unwrap(),
-            )
-            .unwrap();
-
-        std::thread::spawn(move || loop {
-            let mut packet_bytes = receiver.recv().unwrap();
-            let packet = match ClientboundPacket::deserialize_uncompressed_minecraft_packet(packet_bytes.as_mut_slice()) {
-                Ok(packet) => packet,
-                Err(e) => {
-                    log::error!("Failed to parse clientbound packet: {:?}. In {:?}", e, packet_bytes);
-                    continue;
+                Err(state) => {
+                    error!("{} failed to connect on {}:{}: {:?}", username, addr, port, state);
+                    state
                 }
             };
-            let mut bot = bot.lock().unwrap();
-            let response_packets = bot.update(packet);
-            for response_packet in response_packets {
-                let response_packet = match response_packet.serialize_minecraft_packet() {
-                    Ok(response_packet) => response_packet,
-                    Err(e) => {
-                        log::error!("Failed to serialize packet from client {}", e);
-                        continue;
-                    }
-                };
-                sender.send(response_packet).unwrap();
+
+            let delay = match reconnection.next_delay(bot, joined) {
+                Some(delay) => delay,
+                None => return state,
+            };
+            info!("Reconnecting {} in {:?} (attempt {})", username, delay, reconnection.attempt);
+
+            // Sleep by small steps so that a shutdown does not have to wait for the whole delay
+            let wake_up_time = Instant::now() + delay;
+            while Instant::now() < wake_up_time {
+                if bot.lock().unwrap().shutdown_requested {
+                    return state;
+                }
+                std::thread::sleep(wake_up_time.saturating_duration_since(Instant::now()).min(std::time::Duration::from_millis(100)));
+            }
+        }
+    }
+
+    /// Prepares the bot for a new connection.
+    /// Returns the state to stop with if a shutdown was requested in the meantime.
+    fn start_session(bot: &Arc<Mutex<Bot>>, sender: &PacketSender, network_stats: Arc<Mutex<NetworkStats>>) -> Option<ConnectionState> {
+        let mut bot = bot.lock().unwrap();
+        if bot.shutdown_requested {
+            sender.close();
+            return Some(bot.connection_state.clone());
+        }
+        bot.reset_session(sender.clone(), network_stats);
+        None
+    }
+
+    /// Logs the end of a session
+    fn end_session(&mut self, state: &ConnectionState) {
+        self.release_senders();
+        match state {
+            ConnectionState::Disconnected(reason) => info!("Disconnected: {}", reason),
+            state => error!("Connection lost: {:?}", state),
+        }
+    }
+
+    /// Asks the bot to disconnect and to stop reconnecting.
+    /// The bot stops at the next tick.
+    pub fn request_shutdown(&mut self) {
+        self.shutdown_requested = true;
+        if self.connection_state == ConnectionState::Connected {
+            self.connection_state = ConnectionState::Disconnected("Shutdown requested".to_string());
+        }
+    }
+
+    /// Runs the bot on an established connection until it is closed.
+    /// Returns the reason why the session ended.
+    fn run_session(bot: &Arc<Mutex<Bot>>, receiver: Receiver<ConnectionEvent>, sender: PacketSender) -> ConnectionState {
+        let sender2 = sender.clone();
+        let bot2 = Arc::clone(bot);
+        let bot = Arc::clone(bot);
+
+        // Wait for the server to be ready.
+        std::thread::sleep(std::time::Duration::from_millis(500));
+
+        bot.lock().unwrap().send_client_settings();
+
+        let update_thread = std::thread::spawn(move || {
+            while let Ok(event) = receiver.recv() {
+                if !bot.lock().unwrap().handle_connection_event(event, &sender) {
+                    break;
+                }
             }
         });
 
-        loop {
+        let state = loop {
             let start_time = Instant::now();
             {
                 let mut bot = bot2.lock().unwrap();
-                let response_packets = bot.act();
-                for response_packet in response_packets {
-                    let response_packet = match response_packet.serialize_minecraft_packet() {
-                        Ok(response_packet) => response_packet,
-                        Err(e) => {
-                            log::error!("Failed to serialize packet from client {}", e);
-                            continue;
-                        }
-                    };
-                    sender2.send(response_packet).unwrap();
+                if !bot.tick(&sender2) {
+                    break bot.connection_state.clone();
                 }
             }
             let elapsed_time = Instant::now() - start_time;
@@ -122,77 +243,259 @@ impl Bot {
                     .checked_sub(elapsed_time)
                     .unwrap_or_else(|| std::time::Duration::from_millis(0)),
             );
+        };
+
+        // Closing the connection makes the receiving thread report it, which stops the update thread if it is still running.
+        sender2.close();
+        let _ = update_thread.join();
+        bot2.lock().unwrap().end_session(&state);
+        state
+    }
+
+    /// A snapshot of the counters of the current connection.
+    pub fn network_stats(&self) -> NetworkStats {
+        self.network_stats.lock().unwrap().clone()
+    }
+
+    /// The latency of the bot in milliseconds, as measured by the server and shown in the tab list
+    pub fn latency(&self) -> Option<i32> {
+        self.players.get_by_name(&self.config.username).map(|player| player.latency)
+    }
+
+    fn send_client_settings(&mut self) {
+        let locale = self.config.locale.clone();
+        self.send_packet(ServerboundPacket::ClientSettings {
+            locale: &locale,
+            render_distance: self.config.render_distance,
+            chat_mode: ChatMode::Enabled,
+            chat_colors_enabled: self.config.chat_colors,
+            displayed_skin_parts: self.config.displayed_skin_parts,
+            main_hand: self.config.main_hand,
+            disable_text_filtering: true,
+        });
+    }
+
+    /// Drops the senders held by the bot components so that the network can shut down.
+    fn release_senders(&mut self) {
+        let (sender, _receiver) = PacketSender::channel();
+        self.sender = sender.clone();
+        self.entities = Entities::new(sender.clone());
+        self.windows = Windows::new(sender);
+    }
+
+    /// Queues a packet right away instead of returning it from [Bot::update] or [Bot::act].
+    /// Useful when the packet borrows data that does not outlive the call.
+    pub fn send_packet(&mut self, packet: ServerboundPacket) {
+        self.track_digging(&packet);
+        self.sender.send(packet);
+    }
+
+    /// Sends a public chat message, split in several messages if it is too long.
+    /// Messages are throttled and wait for the bot to be connected.
+    pub fn say(&mut self, message: &str) {
+        self.chat.say(message);
+    }
+
+    /// Sends a private message to a player with `/msg`. Fails if the name is not a valid username.
+    pub fn whisper(&mut self, player: &str, message: &str) -> Result<(), String> {
+        self.chat.whisper(player, message)
+    }
+
+    /// Runs a server command, with or without the leading `/`
+    pub fn command(&mut self, command: &str) -> Result<(), String> {
+        self.chat.command(command)
+    }
+
+    /// Remembers the block being dug so that [Bot::stop_actions] can cancel it.
+    /// Every packet leaving the bot goes through here, from [Bot::send_packet] or [Bot::act].
+    fn track_digging(&mut self, packet: &ServerboundPacket) {
+        if let ServerboundPacket::DigBlock { status, location, .. } = packet {
+            self.digging = match status {
+                DiggingState::Started => Some((location.x, location.y as i32, location.z)),
+                _ => None,
+            };
+        }
+    }
+
+    /// Interrupts what the bot is doing: stops moving, cancels digging and closes the windows.
+    /// Called when a mission is cancelled, interrupted or paused.
+    pub fn stop_actions(&mut self, packets: &mut Vec<ServerboundPacket>) {
+        self.physics.input = MovementInput::default();
+        if let Some((x, y, z)) = self.digging.take() {
+            debug!("Cancelling digging at {} {} {}", x, y, z);
+            packets.push(ServerboundPacket::DigBlock {
+                status: DiggingState::Cancelled,
+                location: Position { x, y: y as i16, z },
+                face: BlockFace::Top,
+            });
         }
+        self.windows.close_all();
+    }
+
+    /// Whether hostile mobs can spawn in the open
+    pub fn is_night(&self) -> bool {
+        self.time.is_night()
+    }
+
+    pub fn is_raining(&self) -> bool {
+        self.weather.is_raining()
+    }
+
+    pub fn is_thundering(&self) -> bool {
+        self.weather.is_thundering()
+    }
+
+    /// Whether the bot could sleep in a bed now
+    pub fn can_sleep(&self) -> bool {
+        self.time.is_sleep_time() || self.weather.is_thundering()
+    }
+
+    /// Queues a mission, see [MissionScheduler::submit].
+    /// The scheduler is locked while a mission runs, so the mission is only handed to it at the end of the tick.
+    /// This makes it safe to call from a running mission.
+    pub fn submit_mission(&mut self, name: impl Into<String>, priority: MissionPriority, mission: impl Mission<Result<String, String>> + 'static) -> MissionId {
+        let mission = ScheduledMission::new(name, priority, Box::new(mission));
+        let id = mission.id();
+        self.submitted_missions.push(mission);
+        id
+    }
+
+    /// The name of a connected player
+    pub fn player_name(&self, uuid: u128) -> Option<&str> {
+        self.players.get(uuid).map(|player| player.name.as_str())
+    }
+
+    /// The entity of a connected player, if they are close enough to be seen
+    pub fn player_entity(&self, name: &str) -> Option<i32> {
+        let player = self.players.get_by_name(name)?;
+        self.entities.find_player(player.uuid)
+    }
+
+    fn send_packets(packets: Vec<ServerboundPacket>, sender: &PacketSender) {
+        for packet in packets {
+            sender.send(packet);
+        }
+    }
+
+    /// Handles an event coming from the network.
+    /// Returns false when the session is over.
+    fn handle_connection_event(&mut self, event: ConnectionEvent, sender: &PacketSender) -> bool {
+        let mut packet_bytes = match event {
+            ConnectionEvent::Packet(packet_bytes) => packet_bytes,
+            ConnectionEvent::Closed(state) => {
+                if self.connection_state == ConnectionState::Connected {
+                    self.connection_state = state;
+                }
+                return false;
+            }
+        };
+        let packet = match ClientboundPacket::deserialize_uncompressed_minecraft_packet(packet_bytes.as_mut_slice()) {
+            Ok(packet) => packet,
+            Err(e) => {
+                log::error!("Failed to parse clientbound packet: {:?}. In {:?}", e, packet_bytes);
+                return true;
+            }
+        };
+        let start_time = Instant::now();
+        let response_packets = self.update(packet);
+        if let Some((packet_id, _)) = crate::network::decode_varint(&packet_bytes) {
+            self.network_stats.lock().unwrap().record_update_time(packet_id, start_time.elapsed());
+        }
+        Bot::send_packets(response_packets, sender);
+        self.connection_state == ConnectionState::Connected
+    }
+
+    /// Runs [Bot::act] and sends the resulting packets.
+    /// Returns false when the session is over.
+    fn tick(&mut self, sender: &PacketSender) -> bool {
+        if self.connection_state != ConnectionState::Connected {
+            return false;
+        }
+        let response_packets = self.act();
+        Bot::send_packets(response_packets, sender);
+        true
     }
 
     pub fn act(&mut self) -> Vec<ServerboundPacket> {
         let mut packets = Vec::new();
-        if let Some(position) = self.position.as_mut() {
-            if self.vertical_speed.abs() < 0.003 {
-                self.vertical_speed = 0.0;
-            }
-            if self.map.is_on_ground(position.x, position.y, position.z) {
-                self.vertical_speed = 0.0;
-            } else {
-                self.vertical_speed -= 0.08;
-                self.vertical_speed *= 0.98;
-            }
-        }
+        self.events.next_tick();
+        self.time.tick();
+        // Missions have to press the keys again at every tick
+        self.physics.input = MovementInput::default();
 
         // TODO, replace path with mission
 
-        let mission = Arc::clone(&self.mission);
-        let mut done = false;
-        if let Some(mission) = mission.lock().unwrap().as_mut() {
-            match mission.execute(self, &mut packets) {
-                MissionResult::InProgress => (),
-                MissionResult::Done(Ok(message)) => {
-                    info!("Mission success: {}", message);
-                    done = true;
-                }
-                MissionResult::Done(Err(error)) => {
-                    warn!("Mission failure: {}", error);
-                    done = true;
-                }
-                MissionResult::Outdated => {
-                    error!("Outdated mission");
-                    done = true;
-                },
+        // Missions are paused until the bot has joined the game and knows its position (after a reconnection for example)
+        let joined = self.self_entity_id.is_some() && self.position.is_some();
+        if joined {
+            let missions = Arc::clone(&self.missions);
+            missions.lock().unwrap().execute(self, &mut packets);
+        }
+        if !self.submitted_missions.is_empty() {
+            let mut missions = self.missions.lock().unwrap();
+            for mission in self.submitted_missions.drain(..) {
+                missions.submit_scheduled(mission);
             }
         }
-        if done {
-            *mission.lock().unwrap() = None;
+
+        if joined {
+            if let Some(message) = self.chat.pop_ready(Instant::now()) {
+                self.send_packet(ServerboundPacket::ChatMessage { message: &message });
+            }
         }
 
         if let Some(position) = self.position.as_mut() {
-            let max_negative_speed = self.map.max_fall(position.x, position.y, position.z);
-            //trace!("{} {} {} {}", self.map.is_on_ground(position.x, position.y, position.z), max_negative_speed, self.vertical_speed, position.y);
-            if self.vertical_speed < max_negative_speed {
-                self.vertical_speed = max_negative_speed;
+            // Like the vanilla client, the bot does not move until the terrain around it is loaded
+            if self.map.is_loaded(position.x.floor() as i32, position.z.floor() as i32) {
+                self.physics.tick(&self.map, position);
             }
-            position.y += self.vertical_speed;
-            packets.push(ServerboundPacket::PlayerPosition {
+            packets.push(ServerboundPacket::PlayerPositionAndRotation {
                 x: position.x,
                 y: position.y,
                 z: position.z,
-                on_ground: self.map.is_on_ground(position.x, position.y, position.z),
+                yaw: position.yaw,
+                pitch: position.pitch,
+                on_ground: self.physics.on_ground,
             });
         }
 
+        for packet in &packets {
+            self.track_digging(packet);
+        }
         packets
     }
 
+    fn emit_block_changed(&mut self, x: i32, y: i32, z: i32) {
+        let block = self.map.get_block(x, y, z);
+        self.events.emit(BotEvent::BlockChanged { x, y, z, block });
+    }
+
+    fn emit_entity_moved(&mut self, entity_id: i32) {
+        if let Some(entity) = self.entities.get(entity_id) {
+            let position = entity.get_position();
+            self.events.emit(BotEvent::EntityMoved { entity_id, position });
+        }
+    }
+
     pub fn update(&mut self, packet: ClientboundPacket) -> Vec<ServerboundPacket> {
         let mut responses = Vec::new();
         match packet {
             ClientboundPacket::KeepAlive { keep_alive_id } => {
                 responses.push(ServerboundPacket::KeepAlive { keep_alive_id });
             }
+            ClientboundPacket::Disconnect { reason } => {
+                let reason = plain_text(reason);
+                warn!("Disconnected by the server: {}", reason);
+                self.connection_state = ConnectionState::Disconnected(reason);
+            }
             ClientboundPacket::ChunkData { value } => {
+                let (chunk_x, chunk_z) = (value.chunk_x, value.chunk_z);
                 self.map.load_chunk(value);
+                self.events.emit(BotEvent::ChunkLoaded { chunk_x, chunk_z });
             }
             ClientboundPacket::UnloadChunk { chunk_x, chunk_y } => {
                 self.map.unload_chunk(chunk_x, chunk_y);
+                self.events.emit(BotEvent::ChunkUnloaded { chunk_x, chunk_z: chunk_y });
             }
             ClientboundPacket::PlayerPositionAndLook {
                 mut x,
@@ -222,8 +525,9 @@ impl Bot {
                     }
                 };
                 self.position = Some(PlayerPosition { x, y, z, yaw, pitch });
-                self.vertical_speed = 0.0;
+                self.physics.reset();
                 warn!("Bot teleported at {:?}", self.position);
+                self.events.emit(BotEvent::Teleported { x, y, z });
                 responses.push(ServerboundPacket::TeleportConfirm { teleport_id });
                 responses.push(ServerboundPacket::PlayerPositionAndRotation {
                     x,
@@ -238,20 +542,61 @@ impl Bot {
                 debug!("Spawn position set to {:?}", location);
                 self.spawn_position = Some(location);
             }
-            ClientboundPacket::JoinGame { player_id, world_name, .. } => {
+            ClientboundPacket::JoinGame {
+                player_id,
+                world_name,
+                dimension,
+                ..
+            } => {
                 info!("Joined a world! ({}) {}", world_name, player_id);
-                self.entities.add_self(player_id);
                 self.self_entity_id = Some(player_id);
-                self.world_name = Some(world_name.to_string());
+                self.change_world(world_name, DimensionType::from_nbt(&dimension));
+                let brand = self.plugin_channels.brand_payload();
+                self.send_packet(ServerboundPacket::PluginMessage {
+                    channel: "minecraft:brand",
+                    data: RawBytes { data: &brand },
+                });
+            }
+            ClientboundPacket::Respawn { dimension, world_name, .. } => {
+                let dimension_changed = self.world_name.as_deref() != Some(world_name);
+                info!("Respawned in {}", world_name);
+                self.change_world(world_name, DimensionType::from_nbt(&dimension));
+                if dimension_changed {
+                    // The running mission targets blocks of the previous dimension
+                    if let Some(id) = self.missions.lock().unwrap().cancel_current() {
+                        info!("Mission {} cancelled since the bot changed dimension", id);
+                    }
+                }
+                self.events.emit(BotEvent::Respawned {
+                    world_name: world_name.to_string(),
+                    dimension_changed,
+                });
+            }
+            ClientboundPacket::TimeUpdate { world_age, time_of_day } => {
+                self.time = WorldTime { world_age, time_of_day };
+            }
+            ClientboundPacket::ChangeGameState { reason, value } => {
+                if self.weather.handle_game_state(reason as u8, value) {
+                    self.events.emit(BotEvent::WeatherChanged {
+                        raining: self.weather.is_raining(),
+                        thundering: self.weather.is_thundering(),
+                    });
+                }
             }
             ClientboundPacket::UpdateHealth { health, food, food_saturation } => {
                 self.health = health;
                 self.food = std::cmp::max(food.0, 0) as u32;
                 self.food_saturation = food_saturation;
+                self.events.emit(BotEvent::HealthChanged {
+                    health,
+                    food: self.food,
+                    food_saturation,
+                });
 
                 if health <= 0.0 {
                     info!("Bot died: respawning...");
-                    self.vertical_speed = 0.0;
+                    self.events.emit(BotEvent::Died);
+                    self.physics.reset();
                     responses.push(ServerboundPacket::ClientStatus {
                         action: minecraft_protocol::components::game_state::ClientStatus::PerformRespawn,
                     });
@@ -270,6 +615,11 @@ impl Bot {
                 for block in blocks.items {
                     let (block, block_x, block_y, block_z) = MultiBlockChange::decode_block(unsafe { std::mem::transmute(block.0) });
                     self.map.set_block_state_complex(chunk_x, chunk_y, chunk_z, block_x, block_y, block_z, block);
+                    self.emit_block_changed(
+                        chunk_x * 16 + block_x as i32,
+                        chunk_y * 16 + block_y as i32,
+                        chunk_z * 16 + block_z as i32,
+                    );
                 }
             }
             ClientboundPacket::BlockChange { location, block_state } => {
@@ -283,52 +633,37 @@ impl Bot {
                 self.map.set_block_state_complex(chunk_x, chunk_y, chunk_z, block_x, block_y, block_z, unsafe {
                     std::mem::transmute(block_state.0)
                 });
+                self.emit_block_changed(location.x, location.y as i32, location.z);
             }
-            ClientboundPacket::ChatMessage {
-                message,
-                position: _,
-                sender: _,
-            } => {
-                if message.contains("dig down") {
-                    *self.mission.lock().unwrap() = Some(Box::new(dig_down(12)));
-                } else if message.contains("test inventory 1") {
-                    self.windows.player_inventory.change_held_item(0);
-                    if let Some(item) = &self.windows.cursor().item {
-                        if item.item_id == Item::PinkWool {
-                            self.windows.click_slot(0, 36);
-                        }
-                    } else {
-                        for (id, slot) in self.windows.player_inventory.get_slots().iter().enumerate() {
-                            if let Some(item) = &slot.item {
-                                if item.item_id == Item::PinkWool {
-                                    self.windows.click_slot(0, id);
-                                    self.windows.click_slot(0, 36);
-                                    break;
-                                }
-                            }
-                        }
+            ClientboundPacket::ChatMessage { message, position: _, sender } => {
+                let message = match ChatComponent::parse(message) {
+                    Ok(message) => message,
+                    Err(e) => {
+                        warn!("{}: {}", e, message);
+                        ChatComponent::text(message)
                     }
-                } else if message.contains("test inventory 2") {
-                    self.windows.player_inventory.change_held_item(0);
-                    if let Some(item) = &self.windows.cursor().item {
-                        if item.item_id == Item::BlueWool {
-                            self.windows.click_slot(0, 36);
-                        }
-                    } else {
-                        for (id, slot) in self.windows.player_inventory.get_slots().iter().enumerate() {
-                            if let Some(item) = &slot.item {
-                                if item.item_id == Item::BlueWool {
-                                    self.windows.click_slot(0, id);
-                                    self.windows.click_slot(0, 36);
-                                    break;
-                                }
-                            }
+                };
+                info!("[chat] {}", message.to_ansi());
+                let content = message.player_message();
+                self.events.emit(BotEvent::ChatReceived { message, sender });
+                if sender != 0 {
+                    if let Some(content) = content {
+                        let commands = Arc::clone(&self.config.commands);
+                        if let Some(reply) = commands.execute(self, sender, &content) {
+                            self.say(&reply);
                         }
                     }
-                } else if message.contains("travel") {
-                    *self.mission.lock().unwrap() = Some(Box::new(travel((-222, 75, 54), 7500)));
-                } else if message.contains("cut trees") {
-                    *self.mission.lock().unwrap() = Some(Box::new(cut_trees(100, 50)));
+                }
+            }
+            ClientboundPacket::PlayerInfo { value } => {
+                self.players.handle_player_info_packet(value);
+            }
+            ClientboundPacket::PluginMessage { channel, data } => {
+                if let Some(response) = self.plugin_channels.handle_play_message(channel, data.data) {
+                    self.send_packet(ServerboundPacket::PluginMessage {
+                        channel,
+                        data: RawBytes { data: &response },
+                    });
                 }
             }
             ClientboundPacket::OpenWindow {
@@ -337,6 +672,10 @@ impl Bot {
                 window_title,
             } => {
                 self.windows.handle_open_window_packet(window_id.0, window_type, window_title);
+                self.events.emit(BotEvent::WindowOpened {
+                    window_id: window_id.0,
+                    title: window_title.to_string(),
+                });
             }
             ClientboundPacket::WindowItems {
                 window_id,
@@ -345,6 +684,7 @@ impl Bot {
                 carried_item,
             } => {
                 self.windows.handle_update_window_items_packet(window_id, slots.items, state_id.0, carried_item);
+                self.events.emit(BotEvent::InventoryChanged { window_id });
             }
             ClientboundPacket::SetSlot {
                 window_id,
@@ -353,6 +693,7 @@ impl Bot {
                 slot_value,
             } => {
                 self.windows.handle_set_slot_packet(window_id, state_id.0, slot_index, slot_value);
+                self.events.emit(BotEvent::InventoryChanged { window_id });
             }
             ClientboundPacket::CloseWindow { window_id } => {
                 self.windows.handle_close_window_packet(window_id);
@@ -376,12 +717,20 @@ impl Bot {
             } => {
                 self.entities
                     .handle_spawn_entity_packet(id.0, uuid, entity_type, x, y, z, pitch, yaw, data, velocity_x, velocity_y, velocity_z);
+                self.events.emit(BotEvent::EntitySpawned {
+                    entity_id: id.0,
+                    position: (x, y, z),
+                });
             }
             ClientboundPacket::EntityMetadata { entity_id, metadata } => {
                 self.entities.handle_entity_metadata_packet(entity_id.0, metadata);
             }
             ClientboundPacket::SpawnPlayer { id, uuid, x, y, z, yaw, pitch } => {
                 self.entities.handle_spawn_player_packet(id.0, uuid, x, y, z, yaw, pitch);
+                self.events.emit(BotEvent::EntitySpawned {
+                    entity_id: id.0,
+                    position: (x, y, z),
+                });
             }
             ClientboundPacket::SpawnLivingEntity {
                 id,
@@ -399,9 +748,17 @@ impl Bot {
             } => {
                 self.entities
                     .handle_spawn_living_entity_packet(id.0, uuid, entity_type, x, y, z, yaw, pitch, head_pitch, velocity_x, velocity_y, velocity_z);
+                self.events.emit(BotEvent::EntitySpawned {
+                    entity_id: id.0,
+                    position: (x, y, z),
+                });
             }
             ClientboundPacket::SpawnExperienceOrb { id, x, y, z, count } => {
                 self.entities.handle_spawn_experience_orb_packet(id.0, x, y, z, count);
+                self.events.emit(BotEvent::EntitySpawned {
+                    entity_id: id.0,
+                    position: (x, y, z),
+                });
             }
             ClientboundPacket::SpawnPainting {
                 id,
@@ -411,6 +768,10 @@ impl Bot {
                 direction,
             } => {
                 self.entities.handle_spawn_painting_packet(id.0, uuid, motive, location, direction);
+                self.events.emit(BotEvent::EntitySpawned {
+                    entity_id: id.0,
+                    position: (location.x as f64, location.y as f64, location.z as f64),
+                });
             }
             ClientboundPacket::EntityAnimation { .. } | ClientboundPacket::EntityStatus { .. } | ClientboundPacket::EntityHeadLook { .. } => {
                 // Unsupported as it is primarly used in animations
@@ -423,6 +784,7 @@ impl Bot {
                 on_ground,
             } => {
                 self.entities.handle_entity_position_packet(entity_id.0, delta_x, delta_y, delta_z, on_ground);
+                self.emit_entity_moved(entity_id.0);
             }
             ClientboundPacket::EntityPositionAndRotation {
                 entity_id,
@@ -435,10 +797,14 @@ impl Bot {
             } => {
                 self.entities
                     .handle_entity_position_and_rotation_packet(entity_id.0, delta_x, delta_y, delta_z, yaw, pitch, on_ground);
+                self.emit_entity_moved(entity_id.0);
             }
             ClientboundPacket::DestroyEntities { entity_ids } => {
-                self.entities
-                    .handle_destroy_entities_packet(entity_ids.items.iter().map(|varint| varint.0).collect());
+                let entity_ids: Vec<i32> = entity_ids.items.iter().map(|varint| varint.0).collect();
+                self.entities.handle_destroy_entities_packet(entity_ids.clone());
+                for entity_id in entity_ids {
+                    self.events.emit(BotEvent::EntityRemoved { entity_id });
+                }
             }
             ClientboundPacket::RemoveEntityEffect { entity_id, effect } => {
                 self.entities.handle_remove_entity_effect_packet(entity_id.0, effect);
@@ -464,6 +830,7 @@ impl Bot {
                 on_ground,
             } => {
                 self.entities.handle_teleport_entity_packet(entity_id.0, x, y, z, yaw, pitch, on_ground);
+                self.emit_entity_moved(entity_id.0);
             }
             ClientboundPacket::EntityAttributes { entity_id, attributes } => {
                 self.entities.handle_entity_attributes_packet(entity_id.0, attributes);
@@ -473,3 +840,170 @@ impl Bot {
         responses
     }
 }
+
+#[cfg(feature = "async")]
+impl Bot {
+    /// Same as [Bot::create] but the bot runs as tokio tasks instead of four threads.
+    /// Spawn one future per bot to host many bots in the same process.
+    pub async fn create_async(config: BotConfig) -> ConnectionState {
+        let (sender, _receiver) = PacketSender::channel();
+        let bot = Arc::new(Mutex::new(Bot::new(config, sender)));
+        Bot::run_async(&bot).await
+    }
+
+    /// Same as [Bot::run] but on the tokio runtime.
+    /// The bot state is only locked for the time of an update, so many bots can share the async workers.
+    pub(crate) async fn run_async(bot: &Arc<Mutex<Bot>>) -> ConnectionState {
+        let BotConfig {
+            addr,
+            port,
+            username,
+            connection_options,
+            reconnect_policy,
+            ..
+        } = bot.lock().unwrap().config.clone();
+        let mut reconnection = Reconnection::new(reconnect_policy);
+
+        loop {
+            debug!("Connecting {} to {}:{}", username, addr, port);
+            let network_stats = Arc::new(Mutex::new(NetworkStats::default()));
+            let mut joined = false;
+            let state = match crate::network::connect_async(addr.clone(), port, username.clone(), connection_options.clone(), Arc::clone(&network_stats)).await {
+                Ok((receiver, sender)) => {
+                    info!("{} is connected on {}:{}", username, addr, port);
+                    if let Some(state) = Bot::start_session(bot, &sender, network_stats) {
+                        return state;
+                    }
+                    let state = Bot::run_session_async(bot, receiver, sender).await;
+                    joined = bot.lock().unwrap().self_entity_id.is_some();
+                    state
+                }
+                Err(state) => {
+                    error!("{} failed to connect on {}:{}: {:?}", username, addr, port, state);
+                    state
+                }
+            };
+
+            let delay = match reconnection.next_delay(bot, joined) {
+                Some(delay) => delay,
+                None => return state,
+            };
+            info!("Reconnecting {} in {:?} (attempt {})", username, delay, reconnection.attempt);
+            let wake_up_time = Instant::now() + delay;
+            while Instant::now() < wake_up_time {
+                if bot.lock().unwrap().shutdown_requested {
+                    return state;
+                }
+                tokio::time::sleep(wake_up_time.saturating_duration_since(Instant::now()).min(std::time::Duration::from_millis(100))).await;
+            }
+        }
+    }
+
+    async fn run_session_async(
+        bot: &Arc<Mutex<Bot>>,
+        mut receiver: tokio::sync::mpsc::UnboundedReceiver<ConnectionEvent>,
+        sender: PacketSender,
+    ) -> ConnectionState {
+        // Wait for the server to be ready.
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+        bot.lock().unwrap().send_client_settings();
+
+        let update_bot = Arc::clone(bot);
+        let update_sender = sender.clone();
+        let update_task = tokio::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                let keep_going = update_bot.lock().unwrap().handle_connection_event(event, &update_sender);
+                if !keep_going {
+                    break;
+                }
+            }
+        });
+
+        let mut interval = tokio::time::interval(std::time::Duration::from_millis(50));
+        let state = loop {
+            interval.tick().await;
+            let state = {
+                let mut bot = bot.lock().unwrap();
+                match bot.tick(&sender) {
+                    true => None,
+                    false => Some(bot.connection_state.clone()),
+                }
+            };
+            if let Some(state) = state {
+                break state;
+            }
+        };
+
+        sender.close();
+        let _ = update_task.await;
+        bot.lock().unwrap().end_session(&state);
+        state
+    }
+}
+
+/// Counts the failed connections in a row to pick the reconnection delays.
+/// Shared by [Bot::run] and the async runner.
+struct Reconnection {
+    policy: ReconnectPolicy,
+    attempt: u32,
+}
+
+impl Reconnection {
+    fn new(policy: ReconnectPolicy) -> Reconnection {
+        Reconnection { policy, attempt: 0 }
+    }
+
+    /// Called when a session ended or a connection failed.
+    /// Returns how long to wait before reconnecting, or `None` to stop.
+    fn next_delay(&mut self, bot: &Arc<Mutex<Bot>>, joined: bool) -> Option<Duration> {
+        // Only count consecutive failures: a bot that managed to join the game starts over
+        if joined {
+            self.attempt = 0;
+        }
+        if bot.lock().unwrap().shutdown_requested || !self.policy.should_retry(self.attempt) {
+            return None;
+        }
+        let delay = self.policy.delay(self.attempt);
+        self.attempt += 1;
+        Some(delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dig(status: DiggingState) -> ServerboundPacket<'static> {
+        ServerboundPacket::DigBlock {
+            status,
+            location: Position { x: 1, y: 2, z: 3 },
+            face: BlockFace::Top,
+        }
+    }
+
+    fn cancels_digging(packets: &[ServerboundPacket]) -> bool {
+        packets.iter().any(|packet| matches!(packet, ServerboundPacket::DigBlock { status: DiggingState::Cancelled, .. }))
+    }
+
+    #[test]
+    fn test_digs_are_tracked() {
+        let (sender, _receiver) = PacketSender::channel();
+        let mut bot = Bot::new(BotConfig::new("localhost", 25565, "bot"), sender);
+
+        // Sent directly
+        bot.send_packet(dig(DiggingState::Started));
+        assert_eq!(bot.digging, Some((1, 2, 3)));
+        let mut packets = Vec::new();
+        bot.stop_actions(&mut packets);
+        assert!(cancels_digging(&packets));
+        assert_eq!(bot.digging, None);
+
+        // Finished before being stopped
+        bot.send_packet(dig(DiggingState::Started));
+        bot.send_packet(dig(DiggingState::Finished));
+        let mut packets = Vec::new();
+        bot.stop_actions(&mut packets);
+        assert!(!cancels_digging(&packets));
+    }
+}
@@ -0,0 +1,252 @@
+use crate::network::{ConnectionOptions, ConnectionState, NetworkStats, ReconnectPolicy};
+use crate::*;
+use minecraft_protocol::components::slots::MainHand;
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    thread::JoinHandle,
+};
+
+/// Everything needed to launch a bot.
+#[derive(Clone)]
+pub struct BotConfig {
+    pub addr: String,
+    pub port: u16,
+    pub username: String,
+    /// Sent to the server in the client settings, like "en_US"
+    pub locale: String,
+    /// In chunks
+    pub render_distance: u8,
+    pub chat_colors: bool,
+    /// Bit mask of the displayed skin parts (cape, jacket, sleeves...)
+    pub displayed_skin_parts: u8,
+    pub main_hand: MainHand,
+    pub connection_options: ConnectionOptions,
+    pub reconnect_policy: ReconnectPolicy,
+    /// The chat commands the bot answers to
+    pub commands: Arc<CommandRegistry>,
+    /// Who can run which commands
+    pub permissions: Permissions,
+}
+
+impl BotConfig {
+    pub fn new(addr: impl Into<String>, port: u16, username: impl Into<String>) -> BotConfig {
+        BotConfig {
+            addr: addr.into(),
+            port,
+            username: username.into(),
+            locale: "en_US".to_string(),
+            render_distance: 32,
+            chat_colors: true,
+            displayed_skin_parts: 127,
+            main_hand: MainHand::Right,
+            connection_options: ConnectionOptions::default(),
+            reconnect_policy: ReconnectPolicy::default(),
+            commands: Arc::new(CommandRegistry::default()),
+            permissions: Permissions::new(),
+        }
+    }
+}
+
+/// Builds a [BotConfig] and launches the bot.
+///
+/// ```ignore
+/// let handle = BotBuilder::new("bot").server("127.0.0.1", 25565).render_distance(8).spawn();
+/// ```
+pub struct BotBuilder {
+    config: BotConfig,
+    missions: Vec<(String, MissionPriority, Box<dyn Mission<Result<String, String>>>)>,
+}
+
+impl BotBuilder {
+    /// A bot connecting to `localhost:25565` by default
+    pub fn new(username: impl Into<String>) -> BotBuilder {
+        BotBuilder {
+            config: BotConfig::new("127.0.0.1", 25565, username),
+            missions: Vec::new(),
+        }
+    }
+
+    pub fn server(mut self, addr: impl Into<String>, port: u16) -> BotBuilder {
+        self.config.addr = addr.into();
+        self.config.port = port;
+        self
+    }
+
+    pub fn locale(mut self, locale: impl Into<String>) -> BotBuilder {
+        self.config.locale = locale.into();
+        self
+    }
+
+    pub fn render_distance(mut self, render_distance: u8) -> BotBuilder {
+        self.config.render_distance = render_distance;
+        self
+    }
+
+    pub fn chat_colors(mut self, chat_colors: bool) -> BotBuilder {
+        self.config.chat_colors = chat_colors;
+        self
+    }
+
+    pub fn displayed_skin_parts(mut self, displayed_skin_parts: u8) -> BotBuilder {
+        self.config.displayed_skin_parts = displayed_skin_parts;
+        self
+    }
+
+    pub fn main_hand(mut self, main_hand: MainHand) -> BotBuilder {
+        self.config.main_hand = main_hand;
+        self
+    }
+
+    pub fn connection_options(mut self, connection_options: ConnectionOptions) -> BotBuilder {
+        self.config.connection_options = connection_options;
+        self
+    }
+
+    pub fn reconnect_policy(mut self, reconnect_policy: ReconnectPolicy) -> BotBuilder {
+        self.config.reconnect_policy = reconnect_policy;
+        self
+    }
+
+    pub fn commands(mut self, commands: CommandRegistry) -> BotBuilder {
+        self.config.commands = Arc::new(commands);
+        self
+    }
+
+    pub fn permissions(mut self, permissions: Permissions) -> BotBuilder {
+        self.config.permissions = permissions;
+        self
+    }
+
+    /// Allows a player to run the commands requiring `permission`, or every command with `*`
+    pub fn grant(mut self, player: u128, permission: impl Into<String>) -> BotBuilder {
+        self.config.permissions.grant(player, permission);
+        self
+    }
+
+    /// A mission to start as soon as the bot has joined the game, shown as `name` in the mission list.
+    /// Can be called several times, the missions then run by `priority`, see [MissionScheduler].
+    pub fn mission(mut self, name: impl Into<String>, priority: MissionPriority, mission: impl Mission<Result<String, String>> + 'static) -> BotBuilder {
+        self.missions.push((name.into(), priority, Box::new(mission)));
+        self
+    }
+
+    pub fn config(&self) -> &BotConfig {
+        &self.config
+    }
+
+    pub fn build(self) -> BotConfig {
+        self.config
+    }
+
+    /// Launches the bot on a new thread.
+    pub fn spawn(self) -> BotHandle {
+        BotHandle::spawn_with_missions(self.config, self.missions)
+    }
+}
+
+/// Controls a bot running on its own thread.
+pub struct BotHandle {
+    bot: Arc<Mutex<Bot>>,
+    thread: JoinHandle<ConnectionState>,
+}
+
+impl BotHandle {
+    /// Launches a bot on a new thread and returns immediately.
+    pub fn spawn(config: BotConfig) -> BotHandle {
+        BotHandle::spawn_with_missions(config, Vec::new())
+    }
+
+    fn spawn_with_missions(config: BotConfig, missions: Vec<(String, MissionPriority, Box<dyn Mission<Result<String, String>>>)>) -> BotHandle {
+        let (sender, _receiver) = crate::network::PacketSender::channel();
+        let bot = Bot::new(config, sender);
+        for (name, priority, mission) in missions {
+            bot.missions.lock().unwrap().submit(name, priority, mission);
+        }
+        let bot = Arc::new(Mutex::new(bot));
+        let bot2 = Arc::clone(&bot);
+        let thread = std::thread::spawn(move || Bot::run(&bot2));
+        BotHandle { bot, thread }
+    }
+
+    /// Gives access to the whole bot state.
+    /// The bot is paused while the guard is held so keep it short.
+    pub fn lock(&self) -> MutexGuard<Bot> {
+        self.bot.lock().unwrap()
+    }
+
+    pub fn connection_state(&self) -> ConnectionState {
+        self.lock().connection_state.clone()
+    }
+
+    /// Whether the bot is in game (it might be waiting for a reconnection)
+    pub fn is_joined(&self) -> bool {
+        let bot = self.lock();
+        bot.connection_state == ConnectionState::Connected && bot.self_entity_id.is_some()
+    }
+
+    pub fn position(&self) -> Option<(f64, f64, f64)> {
+        self.lock().position.as_ref().map(|position| (position.x, position.y, position.z))
+    }
+
+    pub fn health(&self) -> f32 {
+        self.lock().health
+    }
+
+    pub fn network_stats(&self) -> NetworkStats {
+        self.lock().network_stats()
+    }
+
+    /// Queues a mission, see [MissionScheduler::submit]
+    pub fn submit_mission(&self, name: impl Into<String>, priority: MissionPriority, mission: impl Mission<Result<String, String>> + 'static) -> MissionId {
+        self.missions().lock().unwrap().submit(name, priority, Box::new(mission))
+    }
+
+    /// Cancels a running or pending mission, see [MissionScheduler::cancel]
+    pub fn cancel_mission(&self, id: MissionId) -> bool {
+        self.missions().lock().unwrap().cancel(id)
+    }
+
+    pub fn pause_missions(&self) {
+        self.missions().lock().unwrap().pause();
+    }
+
+    pub fn resume_missions(&self) {
+        self.missions().lock().unwrap().resume();
+    }
+
+    /// The mission scheduler, to inspect the running, pending and finished missions.
+    /// It can be locked without pausing the bot, except while a mission is running.
+    pub fn missions(&self) -> Arc<Mutex<MissionScheduler>> {
+        Arc::clone(&self.lock().missions)
+    }
+
+    /// Queues a public chat message, see [Bot::say]
+    pub fn send_chat(&self, message: &str) {
+        self.lock().say(message);
+    }
+
+    /// Queues a private message, see [Bot::whisper]
+    pub fn whisper(&self, player: &str, message: &str) -> Result<(), String> {
+        self.lock().whisper(player, message)
+    }
+
+    /// Queues a server command, see [Bot::command]
+    pub fn command(&self, command: &str) -> Result<(), String> {
+        self.lock().command(command)
+    }
+
+    /// Disconnects the bot and waits for its thread to stop.
+    /// Returns how the last session ended.
+    pub fn shutdown(self) -> ConnectionState {
+        self.lock().request_shutdown();
+        self.join()
+    }
+
+    /// Waits until the bot stops by itself (when it cannot reconnect anymore).
+    pub fn join(self) -> ConnectionState {
+        match self.thread.join() {
+            Ok(state) => state,
+            Err(_) => ConnectionState::Errored("The bot thread panicked".to_string()),
+        }
+    }
+}
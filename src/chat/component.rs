@@ -0,0 +1,493 @@
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatColor {
+    Black,
+    DarkBlue,
+    DarkGreen,
+    DarkAqua,
+    DarkRed,
+    DarkPurple,
+    Gold,
+    Gray,
+    DarkGray,
+    Blue,
+    Green,
+    Aqua,
+    Red,
+    LightPurple,
+    Yellow,
+    White,
+    Rgb(u8, u8, u8),
+}
+
+impl ChatColor {
+    const NAMED: [(&'static str, char, ChatColor); 16] = [
+        ("black", '0', ChatColor::Black),
+        ("dark_blue", '1', ChatColor::DarkBlue),
+        ("dark_green", '2', ChatColor::DarkGreen),
+        ("dark_aqua", '3', ChatColor::DarkAqua),
+        ("dark_red", '4', ChatColor::DarkRed),
+        ("dark_purple", '5', ChatColor::DarkPurple),
+        ("gold", '6', ChatColor::Gold),
+        ("gray", '7', ChatColor::Gray),
+        ("dark_gray", '8', ChatColor::DarkGray),
+        ("blue", '9', ChatColor::Blue),
+        ("green", 'a', ChatColor::Green),
+        ("aqua", 'b', ChatColor::Aqua),
+        ("red", 'c', ChatColor::Red),
+        ("light_purple", 'd', ChatColor::LightPurple),
+        ("yellow", 'e', ChatColor::Yellow),
+        ("white", 'f', ChatColor::White),
+    ];
+
+    /// Parses a color name like `dark_red` or a hex color like `#ff8800`
+    pub fn from_name(name: &str) -> Option<ChatColor> {
+        if let Some(hex) = name.strip_prefix('#') {
+            let rgb = u32::from_str_radix(hex, 16).ok().filter(|_| hex.len() == 6)?;
+            return Some(ChatColor::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8));
+        }
+        ChatColor::NAMED.iter().find(|(n, _, _)| *n == name).map(|(_, _, color)| *color)
+    }
+
+    /// Parses the character following `§` in legacy formatting codes
+    pub fn from_legacy_code(code: char) -> Option<ChatColor> {
+        let code = code.to_ascii_lowercase();
+        ChatColor::NAMED.iter().find(|(_, c, _)| *c == code).map(|(_, _, color)| *color)
+    }
+
+    fn ansi_code(self) -> String {
+        match self {
+            ChatColor::Black => "30".to_string(),
+            ChatColor::DarkBlue => "34".to_string(),
+            ChatColor::DarkGreen => "32".to_string(),
+            ChatColor::DarkAqua => "36".to_string(),
+            ChatColor::DarkRed => "31".to_string(),
+            ChatColor::DarkPurple => "35".to_string(),
+            ChatColor::Gold => "33".to_string(),
+            ChatColor::Gray => "37".to_string(),
+            ChatColor::DarkGray => "90".to_string(),
+            ChatColor::Blue => "94".to_string(),
+            ChatColor::Green => "92".to_string(),
+            ChatColor::Aqua => "96".to_string(),
+            ChatColor::Red => "91".to_string(),
+            ChatColor::LightPurple => "95".to_string(),
+            ChatColor::Yellow => "93".to_string(),
+            ChatColor::White => "97".to_string(),
+            ChatColor::Rgb(r, g, b) => format!("38;2;{};{};{}", r, g, b),
+        }
+    }
+}
+
+/// Formatting of a component. `None` means inherited from the parent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ChatStyle {
+    pub color: Option<ChatColor>,
+    pub bold: Option<bool>,
+    pub italic: Option<bool>,
+    pub underlined: Option<bool>,
+    pub strikethrough: Option<bool>,
+    pub obfuscated: Option<bool>,
+}
+
+impl ChatStyle {
+    fn from_json(object: &serde_json::Map<String, Value>) -> ChatStyle {
+        let flag = |name: &str| object.get(name).and_then(|v| v.as_bool());
+        ChatStyle {
+            color: object.get("color").and_then(|c| c.as_str()).and_then(ChatColor::from_name),
+            bold: flag("bold"),
+            italic: flag("italic"),
+            underlined: flag("underlined"),
+            strikethrough: flag("strikethrough"),
+            obfuscated: flag("obfuscated"),
+        }
+    }
+
+    /// This style with the unset properties taken from `parent`
+    pub fn inherit(&self, parent: &ChatStyle) -> ChatStyle {
+        ChatStyle {
+            color: self.color.or(parent.color),
+            bold: self.bold.or(parent.bold),
+            italic: self.italic.or(parent.italic),
+            underlined: self.underlined.or(parent.underlined),
+            strikethrough: self.strikethrough.or(parent.strikethrough),
+            obfuscated: self.obfuscated.or(parent.obfuscated),
+        }
+    }
+
+    /// Applies a legacy `§` code. Returns false if the code is unknown.
+    fn apply_legacy_code(&mut self, code: char) -> bool {
+        if let Some(color) = ChatColor::from_legacy_code(code) {
+            // A color code resets the formatting, like in the vanilla client
+            *self = ChatStyle {
+                color: Some(color),
+                ..ChatStyle::default()
+            };
+            return true;
+        }
+        match code.to_ascii_lowercase() {
+            'k' => self.obfuscated = Some(true),
+            'l' => self.bold = Some(true),
+            'm' => self.strikethrough = Some(true),
+            'n' => self.underlined = Some(true),
+            'o' => self.italic = Some(true),
+            'r' => *self = ChatStyle::default(),
+            _ => return false,
+        }
+        true
+    }
+
+    fn ansi_escape(&self) -> String {
+        let mut codes = vec!["0".to_string()];
+        if let Some(color) = self.color {
+            codes.push(color.ansi_code());
+        }
+        for (enabled, code) in [
+            (self.bold, "1"),
+            (self.italic, "3"),
+            (self.underlined, "4"),
+            (self.obfuscated, "5"),
+            (self.strikethrough, "9"),
+        ]
+        .iter()
+        {
+            if *enabled == Some(true) {
+                codes.push(code.to_string());
+            }
+        }
+        format!("\x1b[{}m", codes.join(";"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClickEvent {
+    /// `open_url`, `run_command`, `suggest_command`, `copy_to_clipboard`...
+    pub action: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatContent {
+    Text(String),
+    /// A translation key and the components to insert in it
+    Translate { key: String, with: Vec<ChatComponent> },
+    /// Keybinds, scores and selectors, which depend on the client. Their raw value is kept.
+    Other(String),
+}
+
+/// A JSON text component, as sent by the server in chat messages, titles, disconnect reasons...
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatComponent {
+    pub content: ChatContent,
+    pub style: ChatStyle,
+    pub click_event: Option<ClickEvent>,
+    pub extra: Vec<ChatComponent>,
+}
+
+/// English translations of the keys that are common in chat messages
+const TRANSLATIONS: &[(&str, &str)] = &[
+    ("chat.type.text", "<%s> %s"),
+    ("chat.type.emote", "* %s %s"),
+    ("chat.type.announcement", "[%s] %s"),
+    ("chat.type.admin", "[%s: %s]"),
+    ("chat.type.team.text", "%s <%s> %s"),
+    ("chat.type.team.sent", "-> %s <%s> %s"),
+    ("commands.message.display.incoming", "%s whispers to you: %s"),
+    ("commands.message.display.outgoing", "You whisper to %s: %s"),
+    ("multiplayer.player.joined", "%s joined the game"),
+    ("multiplayer.player.joined.renamed", "%s (formerly known as %s) joined the game"),
+    ("multiplayer.player.left", "%s left the game"),
+    ("multiplayer.disconnect.kicked", "Kicked by an operator"),
+    ("multiplayer.disconnect.server_shutdown", "Server closed"),
+    ("chat.disabled.options", "Chat disabled in client options"),
+    ("sleep.players_sleeping", "%s/%s players sleeping"),
+    ("sleep.skipping_night", "Sleeping through this night"),
+    ("death.attack.generic", "%s died"),
+    ("death.attack.player", "%s was slain by %s"),
+    ("death.attack.mob", "%s was slain by %s"),
+    ("death.attack.fall", "%s hit the ground too hard"),
+    ("death.attack.lava", "%s tried to swim in lava"),
+    ("death.attack.drown", "%s drowned"),
+    ("death.attack.outOfWorld", "%s fell out of the world"),
+    ("death.fell.accident.generic", "%s fell from a high place"),
+];
+
+impl ChatComponent {
+    pub fn text(text: impl Into<String>) -> ChatComponent {
+        ChatComponent {
+            content: ChatContent::Text(text.into()),
+            style: ChatStyle::default(),
+            click_event: None,
+            extra: Vec::new(),
+        }
+    }
+
+    /// Parses a JSON text component
+    pub fn parse(json: &str) -> Result<ChatComponent, String> {
+        let value: Value = serde_json::from_str(json).map_err(|e| format!("Invalid chat component: {}", e))?;
+        Ok(ChatComponent::from_json(&value))
+    }
+
+    /// Reads a JSON text component. Never fails: unexpected values are rendered as text.
+    pub fn from_json(value: &Value) -> ChatComponent {
+        match value {
+            Value::String(text) => ChatComponent::text(text.as_str()),
+            Value::Array(components) => {
+                // The first component is the parent of the others
+                let mut components = components.iter().map(ChatComponent::from_json);
+                let mut parent = components.next().unwrap_or_else(|| ChatComponent::text(""));
+                parent.extra.extend(components);
+                parent
+            }
+            Value::Object(object) => {
+                let content = if let Some(text) = object.get("text") {
+                    ChatContent::Text(match text {
+                        Value::String(text) => text.clone(),
+                        other => other.to_string(),
+                    })
+                } else if let Some(key) = object.get("translate").and_then(|k| k.as_str()) {
+                    let with = match object.get("with") {
+                        Some(Value::Array(with)) => with.iter().map(ChatComponent::from_json).collect(),
+                        _ => Vec::new(),
+                    };
+                    ChatContent::Translate { key: key.to_string(), with }
+                } else if let Some(keybind) = object.get("keybind").and_then(|k| k.as_str()) {
+                    ChatContent::Other(keybind.to_string())
+                } else if let Some(selector) = object.get("selector").and_then(|s| s.as_str()) {
+                    ChatContent::Other(selector.to_string())
+                } else if let Some(score) = object.get("score") {
+                    ChatContent::Other(score.get("value").and_then(|v| v.as_str()).unwrap_or_default().to_string())
+                } else {
+                    ChatContent::Text(String::new())
+                };
+                let click_event = object.get("clickEvent").and_then(|event| {
+                    Some(ClickEvent {
+                        action: event.get("action")?.as_str()?.to_string(),
+                        value: match event.get("value")? {
+                            Value::String(value) => value.clone(),
+                            other => other.to_string(),
+                        },
+                    })
+                });
+                let extra = match object.get("extra") {
+                    Some(Value::Array(extra)) => extra.iter().map(ChatComponent::from_json).collect(),
+                    _ => Vec::new(),
+                };
+                ChatComponent {
+                    content,
+                    style: ChatStyle::from_json(object),
+                    click_event,
+                    extra,
+                }
+            }
+            other => ChatComponent::text(other.to_string()),
+        }
+    }
+
+    /// What a player wrote, if this is a player chat message or whisper.
+    /// Handles the vanilla translations and the `<name> message` format of Spigot.
+    pub fn player_message(&self) -> Option<String> {
+        match &self.content {
+            ChatContent::Translate { key, with } if key == "chat.type.text" || key == "commands.message.display.incoming" => {
+                with.get(1).map(|message| message.to_plain())
+            }
+            ChatContent::Translate { .. } => None,
+            _ => {
+                let text = self.to_plain();
+                let (_name, message) = text.strip_prefix('<')?.split_once("> ")?;
+                Some(message.to_string())
+            }
+        }
+    }
+
+    /// The text a player sees, without formatting
+    pub fn to_plain(&self) -> String {
+        let mut output = String::new();
+        self.render(&ChatStyle::default(), &mut |text, _| output.push_str(text));
+        output
+    }
+
+    /// The text a player sees, with ANSI escape codes for the colors and formatting
+    pub fn to_ansi(&self) -> String {
+        let mut output = String::new();
+        self.render(&ChatStyle::default(), &mut |text, style| {
+            if !text.is_empty() {
+                output.push_str(&style.ansi_escape());
+                output.push_str(text);
+            }
+        });
+        if !output.is_empty() {
+            output.push_str("\x1b[0m");
+        }
+        output
+    }
+
+    /// Calls `write` with every piece of text and its effective style
+    fn render(&self, parent_style: &ChatStyle, write: &mut dyn FnMut(&str, &ChatStyle)) {
+        let style = self.style.inherit(parent_style);
+        match &self.content {
+            ChatContent::Text(text) | ChatContent::Other(text) => render_legacy(text, &style, write),
+            ChatContent::Translate { key, with } => {
+                let format = TRANSLATIONS.iter().find(|(k, _)| k == key).map(|(_, format)| *format);
+                match format {
+                    Some(format) => render_translation(format, with, &style, write),
+                    None if with.is_empty() => write(key, &style),
+                    None => {
+                        write(key, &style);
+                        for arg in with {
+                            write(" ", &style);
+                            arg.render(&style, write);
+                        }
+                    }
+                }
+            }
+        }
+        for extra in &self.extra {
+            extra.render(&style, write);
+        }
+    }
+}
+
+/// Renders a JSON text component as plain text, or returns it as is if it is not valid JSON
+pub fn plain_text(json: &str) -> String {
+    match ChatComponent::parse(json) {
+        Ok(component) => component.to_plain(),
+        Err(_) => json.to_string(),
+    }
+}
+
+impl std::fmt::Display for ChatComponent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_plain())
+    }
+}
+
+/// Renders text that may contain legacy `§` formatting codes (Spigot still sends them)
+fn render_legacy(text: &str, style: &ChatStyle, write: &mut dyn FnMut(&str, &ChatStyle)) {
+    if !text.contains('§') {
+        write(text, style);
+        return;
+    }
+    let mut style = *style;
+    let mut segment = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '§' {
+            segment.push(c);
+            continue;
+        }
+        // Like vanilla, unknown codes are dropped without changing the style
+        let mut new_style = style;
+        if let Some(code) = chars.next() {
+            if new_style.apply_legacy_code(code) {
+                write(&segment, &style);
+                segment.clear();
+                style = new_style;
+            }
+        }
+    }
+    write(&segment, &style);
+}
+
+/// Fills a translation format with its arguments. Supports `%s`, `%1$s` and `%%`.
+fn render_translation(format: &str, with: &[ChatComponent], style: &ChatStyle, write: &mut dyn FnMut(&str, &ChatStyle)) {
+    let mut next_arg = 0;
+    let mut rest = format;
+    while let Some(idx) = rest.find('%') {
+        write(&rest[..idx], style);
+        rest = &rest[idx + 1..];
+        if let Some(after) = rest.strip_prefix('%') {
+            write("%", style);
+            rest = after;
+            continue;
+        }
+        let arg_idx = match rest.find("$s") {
+            Some(end) if end > 0 && rest[..end].chars().all(|c| c.is_ascii_digit()) => {
+                let position: usize = rest[..end].parse().unwrap_or(1);
+                rest = &rest[end + 2..];
+                position.saturating_sub(1)
+            }
+            _ => match rest.strip_prefix('s') {
+                Some(after) => {
+                    rest = after;
+                    next_arg += 1;
+                    next_arg - 1
+                }
+                None => {
+                    write("%", style);
+                    continue;
+                }
+            },
+        };
+        if let Some(arg) = with.get(arg_idx) {
+            arg.render(style, write);
+        }
+    }
+    write(rest, style);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn styled_segments(component: &ChatComponent) -> Vec<(String, ChatStyle)> {
+        let mut segments = Vec::new();
+        component.render(&ChatStyle::default(), &mut |text, style| {
+            if !text.is_empty() {
+                segments.push((text.to_string(), *style));
+            }
+        });
+        segments
+    }
+
+    #[test]
+    fn test_plain_text() {
+        assert_eq!(plain_text(r#""hello""#), "hello");
+        assert_eq!(plain_text(r#"{"text":"a","extra":["b",{"text":"c"}]}"#), "abc");
+        assert_eq!(plain_text(r#"["a",{"text":"b"}]"#), "ab");
+        assert_eq!(plain_text("not json"), "not json");
+        assert_eq!(plain_text(r#"{"text":42}"#), "42");
+    }
+
+    #[test]
+    fn test_translations() {
+        let join = r#"{"translate":"multiplayer.player.joined","with":[{"text":"Notch"}]}"#;
+        assert_eq!(plain_text(join), "Notch joined the game");
+        let positional = r#"{"translate":"chat.type.text","with":["Notch","100%%"]}"#;
+        assert_eq!(plain_text(positional), "<Notch> 100%%");
+        let unknown = r#"{"translate":"some.key","with":["a","b"]}"#;
+        assert_eq!(plain_text(unknown), "some.key a b");
+
+        let chat = ChatComponent::parse(r#"{"translate":"chat.type.text","with":["Notch","!travel 1 2 3"]}"#).unwrap();
+        assert_eq!(chat.player_message().as_deref(), Some("!travel 1 2 3"));
+        let spigot = ChatComponent::parse(r#"{"text":"<jeb_> hi there"}"#).unwrap();
+        assert_eq!(spigot.player_message().as_deref(), Some("hi there"));
+        assert_eq!(ChatComponent::parse(join).unwrap().player_message(), None);
+    }
+
+    #[test]
+    fn test_styles() {
+        let component = ChatComponent::parse(r##"{"text":"a","color":"red","bold":true,"extra":[{"text":"b","color":"#ff8800"}]}"##).unwrap();
+        let segments = styled_segments(&component);
+        assert_eq!(segments[0].1.color, Some(ChatColor::Red));
+        assert_eq!(segments[1].1.color, Some(ChatColor::Rgb(0xff, 0x88, 0x00)));
+        assert_eq!(segments[1].1.bold, Some(true));
+        assert_eq!(ChatColor::from_name("#12345"), None);
+        assert_eq!(ChatColor::from_name("nope"), None);
+    }
+
+    #[test]
+    fn test_legacy_codes() {
+        let segments = styled_segments(&ChatComponent::text("§cred §lbold§r plain"));
+        assert_eq!(segments[0], ("red ".to_string(), ChatStyle { color: Some(ChatColor::Red), ..ChatStyle::default() }));
+        assert_eq!(segments[1].1.bold, Some(true));
+        assert_eq!(segments[2], (" plain".to_string(), ChatStyle::default()));
+
+        // Unknown codes are dropped too
+        assert_eq!(ChatComponent::text("50§x off").to_plain(), "50 off");
+        let segments = styled_segments(&ChatComponent::text("§cred§y still red"));
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].0, "red still red");
+        assert_eq!(ChatComponent::text("trailing§").to_plain(), "trailing");
+    }
+}
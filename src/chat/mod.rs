@@ -0,0 +1,211 @@
+use crate::*;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+mod component;
+pub use component::*;
+
+/// The maximum length of a serverbound chat message, in characters
+pub const MAX_CHAT_MESSAGE_LENGTH: usize = 256;
+
+/// Whether a name is a valid vanilla username: 1 to 16 letters, digits or underscores
+pub fn is_valid_username(name: &str) -> bool {
+    (1..=16).contains(&name.len()) && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Splits a message into parts of at most `max_length` characters.
+/// Lines are kept apart and words are not cut unless they are too long by themselves.
+/// Characters the server refuses (`§` and control characters) are removed.
+pub fn split_message(message: &str, max_length: usize) -> Vec<String> {
+    let max_length = max_length.max(1);
+    let mut parts = Vec::new();
+    for line in message.lines() {
+        let mut part = String::new();
+        let mut part_length = 0;
+        for word in line.split_whitespace() {
+            let word: String = word.chars().filter(|c| *c != '§' && !c.is_control()).collect();
+            let mut word_length = word.chars().count();
+            if word_length == 0 {
+                continue;
+            }
+            if part_length > 0 && part_length + 1 + word_length > max_length {
+                parts.push(std::mem::take(&mut part));
+                part_length = 0;
+            }
+            if part_length > 0 {
+                part.push(' ');
+                part_length += 1;
+            }
+            let mut chars = word.chars();
+            while part_length + word_length > max_length {
+                let taken = max_length - part_length;
+                part.extend(chars.by_ref().take(taken));
+                parts.push(std::mem::take(&mut part));
+                part_length = 0;
+                word_length -= taken;
+            }
+            part.extend(chars);
+            part_length += word_length;
+        }
+        if part_length > 0 {
+            parts.push(part);
+        }
+    }
+    parts
+}
+
+/// Outgoing chat messages waiting to be sent.
+/// Messages are sent one by one with a minimum interval so that the bot does not get kicked for spamming,
+/// and they stay queued while the bot is disconnected.
+pub struct ChatQueue {
+    queue: VecDeque<String>,
+    last_sent: Option<Instant>,
+    /// Minimum time between two messages
+    pub interval: Duration,
+    /// Older messages are dropped when more are queued
+    pub max_queued: usize,
+}
+
+impl ChatQueue {
+    pub fn new() -> ChatQueue {
+        ChatQueue {
+            queue: VecDeque::new(),
+            last_sent: None,
+            interval: Duration::from_millis(1100),
+            max_queued: 100,
+        }
+    }
+
+    fn push(&mut self, message: String) {
+        if self.queue.len() >= self.max_queued {
+            warn!("Too many chat messages queued: dropping {:?}", self.queue.pop_front());
+        }
+        self.queue.push_back(message);
+    }
+
+    /// Queues a public message, split if it is too long.
+    /// Parts starting with `/` are escaped with a `.` so that they are never run as commands, see [ChatQueue::command] for that.
+    pub fn say(&mut self, message: &str) {
+        // One character is kept for the escape
+        for part in split_message(message, MAX_CHAT_MESSAGE_LENGTH - 1) {
+            match part.starts_with('/') {
+                true => self.push(format!(".{}", part)),
+                false => self.push(part),
+            }
+        }
+    }
+
+    /// Queues a private message using `/msg`, split if it is too long.
+    /// This is the only way [ChatQueue] sends a command on behalf of a message,
+    /// so the name is refused if it is not a valid username.
+    pub fn whisper(&mut self, player: &str, message: &str) -> Result<(), String> {
+        if !is_valid_username(player) {
+            return Err(format!("Invalid username {:?}", player));
+        }
+        let prefix = format!("/msg {} ", player);
+        for part in split_message(message, MAX_CHAT_MESSAGE_LENGTH.saturating_sub(prefix.chars().count())) {
+            self.push(format!("{}{}", prefix, part));
+        }
+        Ok(())
+    }
+
+    /// Queues a command. The leading `/` is optional.
+    /// Commands cannot be split so they are refused when too long.
+    pub fn command(&mut self, command: &str) -> Result<(), String> {
+        let command = format!("/{}", command.trim().trim_start_matches('/'));
+        if command.chars().count() > MAX_CHAT_MESSAGE_LENGTH {
+            return Err(format!("Command is longer than {} characters", MAX_CHAT_MESSAGE_LENGTH));
+        }
+        if command.contains(|c: char| c == '§' || c.is_control()) {
+            return Err("Command contains illegal characters".to_string());
+        }
+        self.push(command);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.queue.clear();
+    }
+
+    /// Returns the next message if the interval since the previous one has elapsed
+    pub fn pop_ready(&mut self, now: Instant) -> Option<String> {
+        if let Some(last_sent) = self.last_sent {
+            if now.saturating_duration_since(last_sent) < self.interval {
+                return None;
+            }
+        }
+        let message = self.queue.pop_front()?;
+        self.last_sent = Some(now);
+        Some(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(queue: &mut ChatQueue) -> Vec<String> {
+        let mut messages = Vec::new();
+        let mut now = Instant::now();
+        while let Some(message) = queue.pop_ready(now) {
+            messages.push(message);
+            now += queue.interval;
+        }
+        messages
+    }
+
+    #[test]
+    fn test_say_never_runs_commands() {
+        let mut queue = ChatQueue::new();
+        queue.say("/op Notch");
+        queue.say("hello\n/kill @e");
+        queue.say(&format!("{} /stop", "a".repeat(MAX_CHAT_MESSAGE_LENGTH - 3)));
+        let messages = drain(&mut queue);
+        assert_eq!(messages[..3], ["./op Notch", "hello", "./kill @e"]);
+        assert_eq!(messages[4], "./stop");
+        assert!(messages.iter().all(|message| !message.starts_with('/') && message.chars().count() <= MAX_CHAT_MESSAGE_LENGTH));
+    }
+
+    #[test]
+    fn test_whisper_and_command() {
+        let mut queue = ChatQueue::new();
+        queue.whisper("Notch", "/hi").unwrap();
+        for name in ["", "Not ch", "§4Notch", "Notch\n/op", "Notch_is_too_long"] {
+            assert!(queue.whisper(name, "hi").is_err());
+        }
+        queue.command("gamemode creative").unwrap();
+        queue.command("/time set day").unwrap();
+        assert!(queue.command("say §4red").is_err());
+        assert!(queue.command(&"a".repeat(MAX_CHAT_MESSAGE_LENGTH)).is_err());
+        assert_eq!(drain(&mut queue), ["/msg Notch /hi", "/gamemode creative", "/time set day"]);
+    }
+
+    #[test]
+    fn test_pacing() {
+        let mut queue = ChatQueue::new();
+        queue.say("first");
+        queue.say("second");
+        let now = Instant::now();
+        assert_eq!(queue.pop_ready(now).as_deref(), Some("first"));
+        assert_eq!(queue.pop_ready(now + Duration::from_millis(500)), None);
+        assert_eq!(queue.pop_ready(now + queue.interval).as_deref(), Some("second"));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_split_message() {
+        assert_eq!(split_message("a bc def", 4), ["a bc", "def"]);
+        assert_eq!(split_message("abcdefg", 3), ["abc", "def", "g"]);
+        assert_eq!(split_message("§4red\n\nline", 10), ["4red", "line"]);
+    }
+}
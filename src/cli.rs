@@ -0,0 +1,263 @@
+use crate::network::{ConnectionOptions, PacketRecorder, ReconnectPolicy};
+use crate::*;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use serde::Deserialize;
+use std::sync::{Arc, Mutex};
+
+/// The content of the TOML config file.
+/// Every field is optional and the command line flags take precedence.
+///
+/// ```toml
+/// server = "play.example.com:25565"
+/// username = "bot"
+/// log_level = "info"
+/// mission = "cut_trees 100 50"
+/// operators = ["069a79f4-44e9-4726-a5be-fca90e38aaf5"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    /// `host`, `host:port` or `[ipv6]:port`
+    pub server: Option<String>,
+    pub username: Option<String>,
+    pub log_level: Option<String>,
+    /// Mission to start once the bot has joined, see [parse_mission]
+    pub mission: Option<String>,
+    pub locale: Option<String>,
+    pub render_distance: Option<u8>,
+    /// Ping the server to pick the protocol version when missing
+    pub protocol_version: Option<i32>,
+    /// Maximum number of reconnections in a row, 0 to disable reconnection
+    pub max_retries: Option<u32>,
+    /// Where to record the packets of the session
+    pub record: Option<String>,
+    /// Prefix of the chat commands
+    pub command_prefix: Option<String>,
+    /// UUIDs of the players allowed to run every command
+    pub operators: Option<Vec<String>>,
+}
+
+impl FileConfig {
+    pub fn load(path: &str) -> Result<FileConfig, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| format!("Failed to read config file {}: {}", path, e))?;
+        toml::from_str(&content).map_err(|e| format!("Invalid config file {}: {}", path, e))
+    }
+}
+
+/// Splits `host:port`, using the default port when there is none.
+/// IPv6 addresses are written `[::1]:25565`, or without brackets when there is no port.
+pub fn parse_server_address(address: &str) -> Result<(String, u16), String> {
+    let invalid_port = |_| format!("Invalid port in server address {:?}", address);
+    if let Some(rest) = address.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']').ok_or_else(|| format!("Unclosed bracket in server address {:?}", address))?;
+        return match rest {
+            "" => Ok((host.to_string(), 25565)),
+            rest => match rest.strip_prefix(':') {
+                Some(port) => Ok((host.to_string(), port.parse().map_err(invalid_port)?)),
+                None => Err(format!("Unexpected characters after the address in {:?}", address)),
+            },
+        };
+    }
+    match address.split_once(':') {
+        // Several colons can only be a bare IPv6 address
+        Some((_, rest)) if rest.contains(':') => Ok((address.to_string(), 25565)),
+        Some((host, port)) => Ok((host.to_string(), port.parse().map_err(invalid_port)?)),
+        None => Ok((address.to_string(), 25565)),
+    }
+}
+
+fn app() -> App<'static, 'static> {
+    let server = Arg::with_name("server")
+        .short("s")
+        .long("server")
+        .takes_value(true)
+        .value_name("HOST[:PORT]")
+        .help("Address of the server [default: 127.0.0.1:25565]");
+
+    App::new("minecraft-bot")
+        .about("A Minecraft bot")
+        .setting(AppSettings::VersionlessSubcommands)
+        .arg(
+            Arg::with_name("config")
+                .short("c")
+                .long("config")
+                .takes_value(true)
+                .value_name("FILE")
+                .global(true)
+                .help("TOML file to read the settings from"),
+        )
+        .arg(
+            Arg::with_name("log-level")
+                .short("l")
+                .long("log-level")
+                .takes_value(true)
+                .value_name("LEVEL")
+                .global(true)
+                .help("Log filter, like \"info\" or \"minecraft_bot=trace\" [default: RUST_LOG or info]"),
+        )
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Connects the bot to a server (default)")
+                .arg(server.clone())
+                .arg(Arg::with_name("username").short("u").long("username").takes_value(true).help("Name of the bot [default: bot]"))
+                .arg(
+                    Arg::with_name("mission")
+                        .short("m")
+                        .long("mission")
+                        .takes_value(true)
+                        .help("Mission to start after joining, like \"travel -222 75 54\""),
+                )
+                .arg(Arg::with_name("record").long("record").takes_value(true).value_name("FILE").help("Records the packets to a file"))
+                .arg(Arg::with_name("no-reconnect").long("no-reconnect").help("Stops after the first disconnection")),
+        )
+        .subcommand(SubCommand::with_name("ping").about("Prints the status of a server").arg(server))
+        .subcommand(
+            SubCommand::with_name("replay")
+                .about("Replays a packet recording offline")
+                .arg(Arg::with_name("file").required(true).help("Recording made with run --record"))
+                .arg(Arg::with_name("username").short("u").long("username").takes_value(true).help("Name of the bot [default: bot]")),
+        )
+}
+
+fn init_logger(flag: Option<&str>, file_config: &FileConfig) {
+    let mut builder = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"));
+    if let Some(filters) = flag.or_else(|| file_config.log_level.as_deref()) {
+        builder.parse_filters(filters);
+    }
+    builder.init();
+}
+
+/// Parses the command line and runs the requested subcommand.
+/// Returns the exit code of the process.
+pub fn run() -> i32 {
+    let matches = app().get_matches();
+    let file_config = match matches.value_of("config").map(FileConfig::load).transpose() {
+        Ok(file_config) => file_config.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+    init_logger(matches.value_of("log-level"), &file_config);
+
+    let result = match matches.subcommand() {
+        ("ping", Some(matches)) => ping(matches, &file_config),
+        ("replay", Some(matches)) => replay(matches, &file_config),
+        ("run", Some(matches)) => run_bot(matches, &file_config),
+        _ => run_bot(&ArgMatches::default(), &file_config),
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            error!("{}", e);
+            1
+        }
+    }
+}
+
+fn server_address(matches: &ArgMatches, file_config: &FileConfig) -> Result<(String, u16), String> {
+    parse_server_address(matches.value_of("server").or_else(|| file_config.server.as_deref()).unwrap_or("127.0.0.1:25565"))
+}
+
+fn username(matches: &ArgMatches, file_config: &FileConfig) -> String {
+    matches.value_of("username").or_else(|| file_config.username.as_deref()).unwrap_or("bot").to_string()
+}
+
+fn run_bot(matches: &ArgMatches, file_config: &FileConfig) -> Result<(), String> {
+    let (addr, port) = server_address(matches, file_config)?;
+    let mut builder = BotBuilder::new(username(matches, file_config)).server(addr, port);
+
+    let mut connection_options = ConnectionOptions::default();
+    connection_options.protocol_version = file_config.protocol_version;
+    if let Some(path) = matches.value_of("record").or_else(|| file_config.record.as_deref()) {
+        let recorder = PacketRecorder::create(path).map_err(|e| format!("Failed to create recording {}: {}", path, e))?;
+        connection_options.recorder = Some(Arc::new(Mutex::new(recorder)));
+    }
+    builder = builder.connection_options(connection_options);
+
+    let mut reconnect_policy = ReconnectPolicy::default();
+    if let Some(max_retries) = file_config.max_retries {
+        reconnect_policy.max_retries = Some(max_retries);
+    }
+    if matches.is_present("no-reconnect") {
+        reconnect_policy = ReconnectPolicy::disabled();
+    }
+    reconnect_policy.validate()?;
+    builder = builder.reconnect_policy(reconnect_policy);
+
+    if let Some(locale) = &file_config.locale {
+        builder = builder.locale(locale.as_str());
+    }
+    if let Some(render_distance) = file_config.render_distance {
+        builder = builder.render_distance(render_distance);
+    }
+    if let Some(prefix) = &file_config.command_prefix {
+        let mut commands = CommandRegistry::default();
+        commands.prefix = prefix.clone();
+        builder = builder.commands(commands);
+    }
+    for operator in file_config.operators.iter().flatten() {
+        builder = builder.grant(parse_uuid(operator)?, "*");
+    }
+    if let Some(mission) = matches.value_of("mission").or_else(|| file_config.mission.as_deref()) {
+        builder = builder.mission(mission, MissionPriority::Normal, parse_mission(mission)?);
+    }
+
+    match builder.spawn().join() {
+        network::ConnectionState::Errored(e) => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn ping(matches: &ArgMatches, file_config: &FileConfig) -> Result<(), String> {
+    let (addr, port) = server_address(matches, file_config)?;
+    let status = network::ping(&addr, port)?;
+    println!("{}:{}", addr, port);
+    println!("  version: {} (protocol {})", status.version.name, status.version.protocol);
+    println!("  players: {}/{}", status.players.online, status.players.max);
+    for name in status.player_names() {
+        println!("    {}", name);
+    }
+    println!("  motd: {}", status.motd());
+    println!("  latency: {}ms", status.latency.as_millis());
+    Ok(())
+}
+
+fn replay(matches: &ArgMatches, file_config: &FileConfig) -> Result<(), String> {
+    let path = matches.value_of("file").unwrap_or_default();
+    let (bot, report) = crate::replay::replay(path, username(matches, file_config))?;
+    println!("{}", path);
+    println!("  clientbound packets: {} ({} failed to parse)", report.clientbound_packets, report.parse_failures);
+    println!("  recorded serverbound packets: {}", report.recorded_serverbound_packets);
+    println!("  ticks: {}", report.ticks);
+    println!("  sent packets: {}", report.sent_packets.len());
+    println!("  final position: {:?}", bot.position);
+    println!("  final health: {}", bot.health);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_server_address() {
+        assert_eq!(parse_server_address("play.example.com"), Ok(("play.example.com".to_string(), 25565)));
+        assert_eq!(parse_server_address("127.0.0.1:25566"), Ok(("127.0.0.1".to_string(), 25566)));
+        assert_eq!(parse_server_address("[::1]:25566"), Ok(("::1".to_string(), 25566)));
+        assert_eq!(parse_server_address("[::1]"), Ok(("::1".to_string(), 25565)));
+        assert_eq!(parse_server_address("2001:db8::1"), Ok(("2001:db8::1".to_string(), 25565)));
+        assert!(parse_server_address("localhost:port").is_err());
+        assert!(parse_server_address("[::1").is_err());
+        assert!(parse_server_address("[::1]25565").is_err());
+        assert!(parse_server_address("[::1]:99999").is_err());
+    }
+
+    #[test]
+    fn test_missing_protocol_version() {
+        let config: FileConfig = toml::from_str("server = \"[::1]:25565\"").unwrap();
+        assert_eq!(config.protocol_version, None);
+        let config: FileConfig = toml::from_str("protocol_version = 756").unwrap();
+        assert_eq!(config.protocol_version, Some(756));
+    }
+}
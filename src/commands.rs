@@ -0,0 +1,376 @@
+use crate::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Who sent a command
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandContext {
+    /// UUID of the player
+    pub sender: u128,
+    /// Name of the player, if they are in the tab list
+    pub sender_name: Option<String>,
+}
+
+/// The arguments following the command name
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandArgs<'a> {
+    args: Vec<&'a str>,
+}
+
+impl<'a> CommandArgs<'a> {
+    pub fn new(args: Vec<&'a str>) -> CommandArgs<'a> {
+        CommandArgs { args }
+    }
+
+    pub fn len(&self) -> usize {
+        self.args.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.args.is_empty()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&'a str> {
+        self.args.get(idx).copied()
+    }
+
+    /// Everything from the argument `idx`, joined with spaces
+    pub fn rest(&self, idx: usize) -> String {
+        self.args.get(idx..).map(|args| args.join(" ")).unwrap_or_default()
+    }
+
+    pub fn number<T: std::str::FromStr>(&self, idx: usize, name: &str) -> Result<T, String> {
+        let value = self.get(idx).ok_or_else(|| format!("Missing {}", name))?;
+        value.parse().map_err(|_| format!("Invalid {}: {}", name, value))
+    }
+
+    /// Same as [CommandArgs::number] with a default value when the argument is missing
+    pub fn number_or<T: std::str::FromStr>(&self, idx: usize, name: &str, default: T) -> Result<T, String> {
+        match self.get(idx) {
+            Some(_) => self.number(idx, name),
+            None => Ok(default),
+        }
+    }
+
+    /// Three arguments starting at `idx`, like `12 64 -3`.
+    /// `~` and `~5` are relative to `origin` like in vanilla commands.
+    pub fn coordinates(&self, idx: usize, origin: Option<(i32, i32, i32)>) -> Result<(i32, i32, i32), String> {
+        let coordinate = |offset: usize, name: &str, origin: Option<i32>| -> Result<i32, String> {
+            let value = self.get(idx + offset).ok_or_else(|| format!("Missing {} coordinate", name))?;
+            match value.strip_prefix('~') {
+                Some(relative) => {
+                    let origin = origin.ok_or_else(|| "Relative coordinates need a known position".to_string())?;
+                    let relative = match relative {
+                        "" => 0,
+                        relative => relative.parse::<i32>().map_err(|_| format!("Invalid {} coordinate: {}", name, value))?,
+                    };
+                    Ok(origin + relative)
+                }
+                None => value.parse().map_err(|_| format!("Invalid {} coordinate: {}", name, value)),
+            }
+        };
+        Ok((
+            coordinate(0, "x", origin.map(|o| o.0))?,
+            coordinate(1, "y", origin.map(|o| o.1))?,
+            coordinate(2, "z", origin.map(|o| o.2))?,
+        ))
+    }
+
+    /// A player name, checked against the vanilla rules
+    pub fn player_name(&self, idx: usize) -> Result<&'a str, String> {
+        let name = self.get(idx).ok_or_else(|| "Missing player name".to_string())?;
+        if name.is_empty() || name.len() > 16 || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("Invalid player name: {}", name));
+        }
+        Ok(name)
+    }
+}
+
+/// Runs a command. The returned message is sent back in the chat, errors included.
+pub type CommandHandler = Box<dyn Fn(&mut Bot, &CommandContext, &CommandArgs) -> Result<Option<String>, String> + Send + Sync>;
+
+pub struct Command {
+    pub name: String,
+    /// Arguments description, like `<x> <y> <z>`
+    pub usage: String,
+    /// The permission the sender needs. `None` means anyone can run the command.
+    pub permission: Option<String>,
+    handler: CommandHandler,
+}
+
+/// Permissions granted to each player, by UUID.
+/// The `*` permission allows everything.
+#[derive(Debug, Clone, Default)]
+pub struct Permissions {
+    players: HashMap<u128, HashSet<String>>,
+}
+
+impl Permissions {
+    pub fn new() -> Permissions {
+        Permissions::default()
+    }
+
+    pub fn grant(&mut self, player: u128, permission: impl Into<String>) {
+        self.players.entry(player).or_default().insert(permission.into());
+    }
+
+    pub fn revoke(&mut self, player: u128, permission: &str) {
+        if let Some(permissions) = self.players.get_mut(&player) {
+            permissions.remove(permission);
+        }
+    }
+
+    pub fn allows(&self, player: u128, permission: &str) -> bool {
+        match self.players.get(&player) {
+            Some(permissions) => permissions.contains("*") || permissions.contains(permission),
+            None => false,
+        }
+    }
+
+    /// Whether the player was granted at least one permission
+    pub fn grants_any(&self, player: u128) -> bool {
+        self.players.get(&player).map(|permissions| !permissions.is_empty()).unwrap_or(false)
+    }
+}
+
+/// Parses a UUID with or without hyphens
+pub fn parse_uuid(uuid: &str) -> Result<u128, String> {
+    let hex: String = uuid.chars().filter(|c| *c != '-').collect();
+    if hex.len() != 32 {
+        return Err(format!("Invalid UUID: {}", uuid));
+    }
+    u128::from_str_radix(&hex, 16).map_err(|_| format!("Invalid UUID: {}", uuid))
+}
+
+/// The chat commands understood by the bot.
+/// A command is a chat message starting with the prefix, like `!travel 12 64 -3`.
+pub struct CommandRegistry {
+    pub prefix: String,
+    commands: BTreeMap<String, Command>,
+}
+
+impl CommandRegistry {
+    /// A registry without any command
+    pub fn new(prefix: impl Into<String>) -> CommandRegistry {
+        CommandRegistry {
+            prefix: prefix.into(),
+            commands: BTreeMap::new(),
+        }
+    }
+
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        usage: impl Into<String>,
+        permission: Option<&str>,
+        handler: impl Fn(&mut Bot, &CommandContext, &CommandArgs) -> Result<Option<String>, String> + Send + Sync + 'static,
+    ) {
+        let name = name.into();
+        if self.commands.contains_key(&name) {
+            warn!("Command {} registered twice: replacing the first one", name);
+        }
+        self.commands.insert(
+            name.clone(),
+            Command {
+                name,
+                usage: usage.into(),
+                permission: permission.map(|p| p.to_string()),
+                handler: Box::new(handler),
+            },
+        );
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Command> {
+        self.commands.get(name)
+    }
+
+    pub fn commands(&self) -> impl Iterator<Item = &Command> {
+        self.commands.values()
+    }
+
+    /// Splits a message into a command name and its arguments if it starts with the prefix
+    pub fn parse<'a>(&self, message: &'a str) -> Option<(&'a str, CommandArgs<'a>)> {
+        let message = message.trim().strip_prefix(self.prefix.as_str())?;
+        let mut parts = message.split_whitespace();
+        let name = parts.next()?;
+        Some((name, CommandArgs::new(parts.collect())))
+    }
+
+    /// Runs the command contained in a message, if there is one.
+    /// Returns the message to reply.
+    ///
+    /// The permission is checked before anything else is said about the command,
+    /// and unknown commands are ignored when the sender has no permission at all, so strangers cannot probe the bot.
+    pub fn execute(&self, bot: &mut Bot, sender: u128, message: &str) -> Option<String> {
+        let (name, args) = self.parse(message)?;
+        let command = match self.commands.get(name) {
+            Some(command) => command,
+            None if bot.config.permissions.grants_any(sender) => return Some(format!("Unknown command {}{}", self.prefix, name)),
+            None => {
+                debug!("Ignoring unknown command {} from {}", name, bot.player_name(sender).unwrap_or("unknown"));
+                return None;
+            }
+        };
+        if let Some(permission) = &command.permission {
+            if !bot.config.permissions.allows(sender, permission) {
+                warn!("Player {} is not allowed to run {}", bot.player_name(sender).unwrap_or("unknown"), name);
+                return Some(format!("You are not allowed to run {}{}", self.prefix, name));
+            }
+        }
+
+        let context = CommandContext {
+            sender,
+            sender_name: bot.player_name(sender).map(|name| name.to_string()),
+        };
+        debug!("Running command {} {:?} from {:?}", name, args, context.sender_name);
+        match (command.handler)(bot, &context, &args) {
+            Ok(reply) => reply,
+            Err(e) => Some(format!("{} (usage: {}{} {})", e, self.prefix, command.name, command.usage)),
+        }
+    }
+}
+
+impl Default for CommandRegistry {
+    /// The built-in commands with the `!` prefix
+    fn default() -> Self {
+        let mut registry = CommandRegistry::new("!");
+        registry.register("dig_down", "[until_y]", Some("missions"), |bot, _, args| {
+            let until_block = args.number_or(0, "until_y", 12)?;
+            bot.submit_mission(format!("dig_down {}", until_block), MissionPriority::Normal, dig_down(until_block));
+            Ok(None)
+        });
+        registry.register("travel", "<x> <y> <z> [max_work]", Some("missions"), |bot, _, args| {
+            let origin = bot.position.as_ref().map(|p| (p.x.floor() as i32, p.y.floor() as i32, p.z.floor() as i32));
+            let destination = args.coordinates(0, origin)?;
+            let maximum_work_allowed = args.number_or(3, "max_work", 7500)?;
+            bot.submit_mission(
+                format!("travel {} {} {}", destination.0, destination.1, destination.2),
+                MissionPriority::Normal,
+                travel(destination, maximum_work_allowed),
+            );
+            Ok(Some(format!("Traveling to {} {} {}", destination.0, destination.1, destination.2)))
+        });
+        registry.register("cut_trees", "[wood] [sapplings]", Some("missions"), |bot, _, args| {
+            let wood_goal = args.number_or(0, "wood", 100)?;
+            let sappling_goal = args.number_or(1, "sapplings", 50)?;
+            bot.submit_mission(format!("cut_trees {} {}", wood_goal, sappling_goal), MissionPriority::Normal, cut_trees(wood_goal, sappling_goal));
+            Ok(None)
+        });
+        registry.register("hold_wool", "<pink|blue>", Some("debug"), |bot, _, args| {
+            let wool = match args.get(0) {
+                Some("pink") => Item::PinkWool,
+                Some("blue") => Item::BlueWool,
+                Some(color) => return Err(format!("Unsupported color: {}", color)),
+                None => return Err("Missing color".to_string()),
+            };
+            hold_item(bot, wool);
+            Ok(None)
+        });
+        registry.register("missions", "", None, |bot, _, _| {
+            let missions = bot.missions.lock().unwrap();
+            let mut lines: Vec<String> = Vec::new();
+            match missions.current() {
+                Some(current) => lines.push(format!("Running #{} {}", current.id, current.name)),
+                None => lines.push("No running mission".to_string()),
+            }
+            for pending in missions.pending() {
+                lines.push(format!("Pending #{} {} ({:?})", pending.id, pending.name, pending.priority));
+            }
+            if let Some(last) = missions.history().back() {
+                match &last.result {
+                    Ok(message) => lines.push(format!("Last: #{} {} succeeded: {}", last.id, last.name, message)),
+                    Err(error) => lines.push(format!("Last: #{} {} failed: {}", last.id, last.name, error)),
+                }
+            }
+            Ok(Some(lines.join("\n")))
+        });
+        registry.register("cancel", "[id|all]", Some("missions"), |bot, _, args| {
+            let mut missions = bot.missions.lock().unwrap();
+            match args.get(0) {
+                None => match missions.cancel_current() {
+                    Some(id) => Ok(Some(format!("Cancelled mission #{}", id))),
+                    None => Err("No running mission".to_string()),
+                },
+                Some("all") => {
+                    missions.cancel_all();
+                    Ok(Some("Cancelled every mission".to_string()))
+                }
+                Some(_) => {
+                    let id = args.number(0, "id")?;
+                    match missions.cancel(id) {
+                        true => Ok(Some(format!("Cancelled mission #{}", id))),
+                        false => Err(format!("No mission #{}", id)),
+                    }
+                }
+            }
+        });
+        registry.register("pause", "", Some("missions"), |bot, _, _| {
+            bot.missions.lock().unwrap().pause();
+            Ok(Some("Missions paused".to_string()))
+        });
+        registry.register("resume", "", Some("missions"), |bot, _, _| {
+            bot.missions.lock().unwrap().resume();
+            Ok(Some("Missions resumed".to_string()))
+        });
+        registry.register("help", "", None, |bot, context, _| {
+            let registry = std::sync::Arc::clone(&bot.config.commands);
+            let commands: Vec<String> = registry
+                .commands()
+                .filter(|command| command.permission.as_ref().map(|p| bot.config.permissions.allows(context.sender, p)).unwrap_or(true))
+                .map(|command| format!("{}{} {}", registry.prefix, command.name, command.usage).trim_end().to_string())
+                .collect();
+            Ok(Some(commands.join(", ")))
+        });
+        registry
+    }
+}
+
+/// Moves an item to the first hotbar slot and selects it
+fn hold_item(bot: &mut Bot, item_id: Item) {
+    bot.windows.player_inventory.change_held_item(0);
+    if let Some(item) = &bot.windows.cursor().item {
+        if item.item_id == item_id {
+            bot.windows.click_slot(0, 36);
+        }
+    } else {
+        let slot = bot
+            .windows
+            .player_inventory
+            .get_slots()
+            .iter()
+            .position(|slot| slot.item.as_ref().map(|item| item.item_id == item_id).unwrap_or(false));
+        if let Some(id) = slot {
+            bot.windows.click_slot(0, id);
+            bot.windows.click_slot(0, 36);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permissions() {
+        let mut permissions = Permissions::new();
+        assert!(!permissions.grants_any(1));
+        permissions.grant(1, "missions");
+        permissions.grant(2, "*");
+        assert!(permissions.allows(1, "missions"));
+        assert!(!permissions.allows(1, "admin"));
+        assert!(permissions.allows(2, "admin"));
+        assert!(!permissions.allows(3, "missions"));
+        assert!(permissions.grants_any(1));
+        permissions.revoke(1, "missions");
+        assert!(!permissions.grants_any(1));
+    }
+
+    #[test]
+    fn test_parse() {
+        let registry = CommandRegistry::new("!");
+        let (name, args) = registry.parse("  !travel 12 ~ -3 ").unwrap();
+        assert_eq!(name, "travel");
+        assert_eq!(args.coordinates(0, Some((0, 64, 0))), Ok((12, 64, -3)));
+        assert!(registry.parse("travel 12 64 -3").is_none());
+        assert!(registry.parse("!").is_none());
+    }
+}
@@ -7,7 +7,7 @@ use minecraft_protocol::{
     components::paintings::Painting,
 };
 use std::collections::{BTreeMap, HashMap};
-use std::sync::mpsc::Sender;
+use crate::network::PacketSender;
 
 const EMPTY_EQUIPMENT: &EntityEquipment = &EntityEquipment::none();
 // TODO once stable: const NO_ATTRIBUTES: &BTreeMap<String, (f64, Vec<minecraft_protocol::components::entity::EntityAttributeModifier>)> = &BTreeMap::new();
@@ -258,18 +258,30 @@ impl Entity {
 }
 
 pub struct Entities {
-    sender: Sender<Vec<u8>>,
+    sender: PacketSender,
     entities: HashMap<i32, Entity>,
 }
 
 impl Entities {
-    pub fn new(sender: Sender<Vec<u8>>) -> Self {
+    pub fn new(sender: PacketSender) -> Self {
         Entities {
             sender,
             entities: HashMap::new(),
         }
     }
 
+    pub fn get(&self, entity_id: i32) -> Option<&Entity> {
+        self.entities.get(&entity_id)
+    }
+
+    /// The entity id of a player, if they are close enough to be seen
+    pub fn find_player(&self, player_uuid: u128) -> Option<i32> {
+        self.entities.iter().find_map(|(id, entity)| match entity {
+            Entity::Player { uuid, .. } if *uuid == player_uuid => Some(*id),
+            _ => None,
+        })
+    }
+
     pub fn get_items(&self, filter: Option<&[Item]>) -> Vec<(i32, i32, i32)> {
         let mut results = Vec::new();
         for entity in self.entities.values() {
@@ -0,0 +1,112 @@
+use crate::*;
+use std::sync::mpsc;
+
+/// Something that changed in the state of the bot or of its world.
+#[derive(Debug, Clone)]
+pub enum BotEvent {
+    ChatReceived {
+        message: ChatComponent,
+        /// The UUID of the player who sent it, 0 for the system
+        sender: u128,
+    },
+    BlockChanged {
+        x: i32,
+        y: i32,
+        z: i32,
+        block: Block,
+    },
+    ChunkLoaded {
+        chunk_x: i32,
+        chunk_z: i32,
+    },
+    ChunkUnloaded {
+        chunk_x: i32,
+        chunk_z: i32,
+    },
+    EntitySpawned {
+        entity_id: i32,
+        position: (f64, f64, f64),
+    },
+    EntityMoved {
+        entity_id: i32,
+        position: (f64, f64, f64),
+    },
+    EntityRemoved {
+        entity_id: i32,
+    },
+    HealthChanged {
+        health: f32,
+        food: u32,
+        food_saturation: f32,
+    },
+    Died,
+    /// The bot respawned after dying or went to another dimension.
+    /// The blocks and entities of the previous world are forgotten,
+    /// and the running mission is cancelled when the dimension changed.
+    Respawned {
+        world_name: String,
+        dimension_changed: bool,
+    },
+    /// The server moved the bot
+    Teleported {
+        x: f64,
+        y: f64,
+        z: f64,
+    },
+    /// Rain or a thunderstorm started or stopped
+    WeatherChanged {
+        raining: bool,
+        thundering: bool,
+    },
+    InventoryChanged {
+        window_id: i8,
+    },
+    WindowOpened {
+        window_id: i32,
+        title: String,
+    },
+}
+
+/// Dispatches [BotEvent]s to subscribers.
+///
+/// Events can be consumed in two ways:
+/// - [EventBus::subscribe] gives a channel receiving every future event, for code running outside of the bot.
+/// - [EventBus::recent] lists the events of the last tick, for missions. Every mission sees every event.
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: Vec<mpsc::Sender<BotEvent>>,
+    /// Events emitted since the beginning of the current tick
+    pending: Vec<BotEvent>,
+    /// Events emitted during the previous tick
+    recent: Vec<BotEvent>,
+}
+
+impl EventBus {
+    pub fn new() -> EventBus {
+        EventBus::default()
+    }
+
+    /// Receives every event emitted from now on.
+    /// Dropping the receiver unsubscribes.
+    pub fn subscribe(&mut self) -> mpsc::Receiver<BotEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    pub fn emit(&mut self, event: BotEvent) {
+        trace!("Event: {:?}", event);
+        self.subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+        self.pending.push(event);
+    }
+
+    /// The events emitted between the two last ticks
+    pub fn recent(&self) -> &[BotEvent] {
+        &self.recent
+    }
+
+    /// Makes the pending events visible through [EventBus::recent]. Called at the beginning of every tick.
+    pub(crate) fn next_tick(&mut self) {
+        self.recent = std::mem::take(&mut self.pending);
+    }
+}
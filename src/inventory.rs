@@ -3,16 +3,17 @@ use log::*;
 use crate::*;
 use minecraft_protocol::{
     ids::{blocks::Block, items::Item},
-    packets::{play_serverbound::ServerboundPacket, serializer::MinecraftPacketPart, Array},
+    packets::{play_serverbound::ServerboundPacket, Array},
     components::slots::Slot,
     components::slots,
 };
-use std::{collections::BTreeMap, sync::mpsc::Sender};
+use crate::network::PacketSender;
+use std::collections::BTreeMap;
 
 pub struct PlayerInventory {
     slots: [Slot; 46],
     held_item: u8,
-    sender: Sender<Vec<u8>>,
+    sender: PacketSender,
 }
 
 impl PlayerInventory {
@@ -84,13 +85,7 @@ impl PlayerInventory {
             panic!("Failed to change held item: {} is not a valid hotbar item id.", new_held_item);
         }
         self.held_item = new_held_item;
-        self.sender
-            .send(
-                ServerboundPacket::HeldItemChange { slot: new_held_item as i16 }
-                    .serialize_minecraft_packet()
-                    .unwrap(),
-            )
-            .unwrap();
+        self.sender.send(ServerboundPacket::HeldItemChange { slot: new_held_item as i16 });
     }
 
     pub fn get_held_item(&self) -> u8 {
@@ -174,21 +169,15 @@ impl PlayerInventory {
         }
 
         use minecraft_protocol::{components::blocks::BlockFace, components::slots::Hand};
-        self.sender
-            .send(
-                ServerboundPacket::PlaceBlock {
-                    hand: if mainhand { Hand::MainHand } else { Hand::OffHand },
-                    location: Position { x, y: y as i16, z },
-                    face: BlockFace::Top,
-                    cursor_position_x: 0.5,
-                    cursor_position_y: 0.5,
-                    cursor_position_z: 0.5,
-                    inside_block: false,
-                }
-                .serialize_minecraft_packet()
-                .unwrap(),
-            )
-            .unwrap();
+        self.sender.send(ServerboundPacket::PlaceBlock {
+            hand: if mainhand { Hand::MainHand } else { Hand::OffHand },
+            location: Position { x, y: y as i16, z },
+            face: BlockFace::Top,
+            cursor_position_x: 0.5,
+            cursor_position_y: 0.5,
+            cursor_position_z: 0.5,
+            inside_block: false,
+        });
 
         map.set_block(x, y, z, block);
 
@@ -243,12 +232,14 @@ pub struct Windows {
     cursor: Slot,
     carried_item: Slot,
     pub windows: BTreeMap<i8, Window>,
-    sender: Sender<Vec<u8>>,
+    /// Windows opened by the server and not closed yet
+    open_windows: Vec<i8>,
+    sender: PacketSender,
     state_id: i32,
 }
 
 impl Windows {
-    pub fn new(sender: Sender<Vec<u8>>) -> Self {
+    pub fn new(sender: PacketSender) -> Self {
         Windows {
             player_inventory: PlayerInventory {
                 slots: array![Slot {item: None}; 46],
@@ -258,6 +249,7 @@ impl Windows {
             cursor: Slot { item: None },
             carried_item: Slot { item: None },
             windows: BTreeMap::new(),
+            open_windows: Vec::new(),
             sender,
             state_id: -1,
         }
@@ -320,25 +312,28 @@ impl Windows {
             window_id => todo!(),
         });
 
-        self.sender
-            .send(
-                ServerboundPacket::ClickWindowSlot {
-                    window_id,
-                    slot: slot_id as i16,
-                    button: 0,
-                    state_id: VarInt(self.state_id),
-                    mode: 0.into(),
-                    new_slot_values: new_slot_values.into(),
-                    clicked_item: Slot { item },
-                }
-                .serialize_minecraft_packet()
-                .unwrap(),
-            )
-            .unwrap();
+        self.sender.send(ServerboundPacket::ClickWindowSlot {
+            window_id,
+            slot: slot_id as i16,
+            button: 0,
+            state_id: VarInt(self.state_id),
+            mode: 0.into(),
+            new_slot_values: new_slot_values.into(),
+            clicked_item: Slot { item },
+        });
     }
 
     pub fn handle_open_window_packet(&mut self, window_id: i32, window_type: slots::WindowType, window_title: &str) {
         trace!("Opening window {}, called {} (type={:?})", window_id, window_title, window_type);
+        self.open_windows.push(window_id as i8);
+    }
+
+    /// Closes every window opened by the server
+    pub fn close_all(&mut self) {
+        for window_id in self.open_windows.drain(..) {
+            trace!("Closing window {}", window_id);
+            self.sender.send(ServerboundPacket::CloseWindow { window_id });
+        }
     }
 
     pub fn handle_update_window_items_packet(&mut self, window_id: i8, slots: Vec<Slot>, state_id: i32, carried_item: Slot) {
@@ -389,6 +384,7 @@ impl Windows {
     pub fn handle_close_window_packet(&mut self, window_id: i8) {
         trace!("Closing window {}", window_id);
 
+        self.open_windows.retain(|id| *id != window_id);
         if self.windows.remove(&window_id).is_none() {
             warn!("There was no window {}", window_id);
         }
@@ -0,0 +1,30 @@
+//! A Minecraft bot that can be embedded in other programs, see [BotBuilder].
+
+#![allow(clippy::new_without_default)]
+#![allow(clippy::too_many_arguments)]
+#![allow(clippy::result_unit_err)]
+
+pub mod bot;
+pub mod builder;
+pub mod chat;
+pub mod cli;
+pub mod commands;
+pub mod entities;
+pub mod events;
+pub mod inventory;
+pub mod map;
+pub mod missions;
+pub mod network;
+pub mod pathfinder;
+pub mod physics;
+pub mod players;
+pub mod replay;
+pub mod time;
+
+pub use log::*;
+pub use minecraft_protocol::{
+    ids::{blocks::Block, entities::Entity, items::Item},
+    packets::{play_clientbound::ClientboundPacket, play_serverbound::ServerboundPacket, Position, VarInt},
+    components::slots::Slot,
+};
+pub use {bot::*, builder::*, chat::*, commands::*, entities::*, events::*, inventory::*, map::*, missions::*, pathfinder::*, physics::*, players::*, time::*};
@@ -2,17 +2,83 @@ use log::*;
 use minecraft_protocol::{
     components::chunk::{ChunkData, ChunkSection},
     ids::blocks::Block,
+    nbt::NbtTag,
 };
 use std::collections::BTreeMap;
 
+/// The vertical bounds of a dimension, sent by the server in the dimension type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DimensionType {
+    /// The lowest block, a multiple of 16
+    pub min_y: i32,
+    /// The number of blocks from the bottom to the top, a multiple of 16
+    pub height: u32,
+}
+
+impl Default for DimensionType {
+    fn default() -> DimensionType {
+        DimensionType { min_y: 0, height: 256 }
+    }
+}
+
+impl DimensionType {
+    /// Reads the dimension type compound of the JoinGame and Respawn packets.
+    /// Missing values are replaced with those of the default overworld.
+    pub fn from_nbt(dimension: &NbtTag) -> DimensionType {
+        let default = DimensionType::default();
+        let get_int = |name: &str| dimension.as_compound().and_then(|compound| compound.get(name)).and_then(|value| value.as_int()).copied();
+        let min_y = get_int("min_y").unwrap_or_else(|| {
+            warn!("Dimension type has no min_y, using {}", default.min_y);
+            default.min_y
+        });
+        let height = get_int("height").unwrap_or_else(|| {
+            warn!("Dimension type has no height, using {}", default.height);
+            default.height as i32
+        });
+        DimensionType {
+            min_y,
+            height: height.max(0) as u32,
+        }
+    }
+
+    pub fn max_y(&self) -> i32 {
+        self.min_y + self.height as i32 - 1
+    }
+
+    /// The number of chunk sections in a chunk column
+    pub fn section_count(&self) -> usize {
+        (self.height / 16) as usize
+    }
+}
+
 pub struct Map {
-    chunk_columns: BTreeMap<(i32, i32), [Option<ChunkSection>; 16]>,
+    /// Chunk sections from the bottom to the top of the dimension
+    chunk_columns: BTreeMap<(i32, i32), Vec<Option<ChunkSection>>>,
+    dimension: DimensionType,
 }
 
 impl Map {
     pub fn new() -> Map {
+        Map::with_dimension(DimensionType::default())
+    }
+
+    pub fn with_dimension(dimension: DimensionType) -> Map {
         Map {
             chunk_columns: BTreeMap::new(),
+            dimension,
+        }
+    }
+
+    pub fn dimension(&self) -> DimensionType {
+        self.dimension
+    }
+
+    /// The index of a chunk section in its column
+    fn section_index(&self, chunk_y: i32) -> Option<usize> {
+        let index = chunk_y - self.dimension.min_y.div_euclid(16);
+        match index >= 0 && (index as usize) < self.dimension.section_count() {
+            true => Some(index as usize),
+            false => None,
         }
     }
 
@@ -24,16 +90,43 @@ impl Map {
                 return;
             }
         };
-        self.chunk_columns.insert((chunk_data.chunk_x, chunk_data.chunk_z), chunk_sections);
+        self.insert_column(chunk_data.chunk_x, chunk_data.chunk_z, Vec::from(chunk_sections));
         //trace!("Loaded chunk {} {}", chunk_data.chunk_x, chunk_data.chunk_z);
     }
 
+    fn insert_column(&mut self, chunk_x: i32, chunk_z: i32, mut chunk_sections: Vec<Option<ChunkSection>>) {
+        if chunk_sections.len() != self.dimension.section_count() {
+            // Indexing relies on one section per level of the dimension: missing sections are air and extra ones are dropped
+            warn!(
+                "Chunk {} {} has {} sections but the dimension has {}: resizing it",
+                chunk_x,
+                chunk_z,
+                chunk_sections.len(),
+                self.dimension.section_count()
+            );
+            chunk_sections.resize_with(self.dimension.section_count(), || None);
+        }
+        self.chunk_columns.insert((chunk_x, chunk_z), chunk_sections);
+    }
+
     pub fn unload_chunk(&mut self, chunk_x: i32, chunk_z: i32) {
         self.chunk_columns.remove(&(chunk_x, chunk_z));
         //trace!("Unloaded chunk {} {}", chunk_x, chunk_z);
     }
 
-    pub fn get_block(&self, x: i32, y: i32, z: i32) -> Block {
+    /// Loads a chunk column of air, to build maps by hand
+    #[cfg(test)]
+    pub(crate) fn load_empty_chunk(&mut self, chunk_x: i32, chunk_z: i32) {
+        self.insert_column(chunk_x, chunk_z, Vec::new());
+    }
+
+    /// Whether the chunk containing this block column is loaded
+    pub fn is_loaded(&self, x: i32, z: i32) -> bool {
+        self.chunk_columns.contains_key(&(x.div_euclid(16), z.div_euclid(16)))
+    }
+
+    /// The state id of a block, 0 (air) if it is not loaded
+    pub fn get_block_state(&self, x: i32, y: i32, z: i32) -> u32 {
         let x_within_chunk = x.rem_euclid(16);
         let z_within_chunk = z.rem_euclid(16);
         let chunk_x = (x - x_within_chunk) / 16;
@@ -42,22 +135,22 @@ impl Map {
             Some(chunk_column) => chunk_column,
             None => {
                 warn!("The indexed block is not loaded (XYZ = {} {} {})", x, y, z);
-                return Block::Air;
+                return 0;
             }
         };
 
-        if y < 0 {
-            warn!("Map indexed with negative y value ({})", y);
-            return Block::Air;
-        }
-        let y_within_chunk = y % 16;
-        let chunk_y = (y - y_within_chunk) / 16;
-        let chunk_section = match chunk_column.get(chunk_y as usize) {
+        // Blocks above and below the world are air
+        let section_index = match self.section_index(y.div_euclid(16)) {
+            Some(section_index) => section_index,
+            None => return 0,
+        };
+        let y_within_chunk = y.rem_euclid(16);
+        let chunk_section = match chunk_column.get(section_index) {
             Some(Some(chunk_section)) => &chunk_section.blocks,
-            Some(None) => return Block::Air,
+            Some(None) => return 0,
             None => {
                 warn!("Map indexed with out of bound y value");
-                return Block::Air;
+                return 0;
             }
         };
 
@@ -65,11 +158,16 @@ impl Map {
             Some(block_state) => block_state,
             None => {
                 warn!("Missing block in the block array");
-                return Block::Air;
+                return 0;
             }
         };
 
-        match Block::from_state_id(*block_state_id) {
+        *block_state_id
+    }
+
+    pub fn get_block(&self, x: i32, y: i32, z: i32) -> Block {
+        let block_state_id = self.get_block_state(x, y, z);
+        match Block::from_state_id(block_state_id) {
             Some(block) => block,
             None => {
                 warn!("Unknown state_id {}", block_state_id);
@@ -78,234 +176,14 @@ impl Map {
         }
     }
 
-    pub fn is_on_ground(&self, x: f64, y: f64, z: f64) -> bool {
-        let x_floor = x.floor();
-        let x1 = x_floor as i32;
-        let x2 = if x - x_floor > 0.705 {
-            Some(x1 + 1)
-        } else if x - x_floor < 0.295 {
-            Some(x1 - 1)
-        } else {
-            None
-        };
-
-        let z_floor = z.floor();
-        let z1 = z_floor as i32;
-        let z2 = if z - z_floor > 0.705 {
-            Some(z1 + 1)
-        } else if z - z_floor < 0.295 {
-            Some(z1 - 1)
-        } else {
-            None
-        };
-
-        let y = (y - 0.01).floor() as i32;
-        if self.get_block(x1, y, z1).is_blocking() {
-            return true;
-        }
-        if let Some(x2) = x2 {
-            if self.get_block(x2, y, z1).is_blocking() && self.get_block(x2, y + 1, z1).is_air_block() && self.get_block(x2, y + 2, z1).is_air_block()
-            {
-                return true;
-            }
-            if let Some(z2) = z2 {
-                if self.get_block(x2, y, z2).is_blocking()
-                    && self.get_block(x2, y + 1, z2).is_air_block()
-                    && self.get_block(x2, y + 2, z2).is_air_block()
-                {
-                    return true;
-                }
-            }
-        }
-        if let Some(z2) = z2 {
-            if self.get_block(x1, y, z2).is_blocking() && self.get_block(x1, y + 1, z2).is_air_block() && self.get_block(x1, y + 2, z2).is_air_block()
-            {
-                return true;
-            }
-        }
-
-        false
-    }
-
-    pub fn max_west_movement(&self, x: f64, y: f64, z: f64) -> f64 {
-        let y_floor = y.floor();
-        let y1 = y_floor as i32;
-        let y2 = y1 + 1;
-        let y3 = if y - y_floor > 0.2 { Some(y2 + 1) } else { None };
-
-        let z_floor = z.floor();
-        let z1 = z_floor as i32;
-        let z2 = if z - z_floor > 0.7 {
-            Some(z1 + 1)
-        } else if z - z_floor < 0.3 {
-            Some(z1 - 1)
-        } else {
-            None
-        };
-
-        let ax = x;
-        let bx = x.floor();
-        let x = bx as i32 - 1;
-        if self.get_block(x, y1, z1).is_blocking() || self.get_block(x, y2, z1).is_blocking() {
-            return ax - bx - 0.3;
-        }
-        if let Some(y3) = y3 {
-            if self.get_block(x, y3, z1).is_blocking() {
-                return ax - bx - 0.3;
-            }
-            if let Some(z2) = z2 {
-                if self.get_block(x, y3, z2).is_blocking() {
-                    return ax - bx - 0.3;
-                }
-            }
-        }
-        if let Some(z2) = z2 {
-            if self.get_block(x, y1, z2).is_blocking() || self.get_block(x, y2, z2).is_blocking() {
-                return ax - bx - 0.3;
-            }
-        }
-
-        0.2
-    }
-
-    pub fn max_east_movement(&self, x: f64, y: f64, z: f64) -> f64 {
-        let y_floor = y.floor();
-        let y1 = y_floor as i32;
-        let y2 = y1 + 1;
-        let y3 = if y - y_floor > 0.2 { Some(y2 + 1) } else { None };
-
-        let z_floor = z.floor();
-        let z1 = z_floor as i32;
-        let z2 = if z - z_floor > 0.7 {
-            Some(z1 + 1)
-        } else if z - z_floor < 0.3 {
-            Some(z1 - 1)
-        } else {
-            None
-        };
-
-        let ax = x;
-        let bx = x.floor() + 1.0;
-        let x = bx as i32;
-        if self.get_block(x, y1, z1).is_blocking() || self.get_block(x, y2, z1).is_blocking() {
-            return bx - ax - 0.3;
-        }
-        if let Some(y3) = y3 {
-            if self.get_block(x, y3, z1).is_blocking() {
-                return bx - ax - 0.3;
-            }
-            if let Some(z2) = z2 {
-                if self.get_block(x, y3, z2).is_blocking() {
-                    return bx - ax - 0.3;
-                }
-            }
-        }
-        if let Some(z2) = z2 {
-            if self.get_block(x, y1, z2).is_blocking() || self.get_block(x, y2, z2).is_blocking() {
-                return bx - ax - 0.3;
-            }
-        }
-
-        0.2
-    }
-
-    pub fn max_south_movement(&self, x: f64, y: f64, z: f64) -> f64 {
-        let y_floor = y.floor();
-        let y1 = y_floor as i32;
-        let y2 = y1 + 1;
-        let y3 = if y - y_floor > 0.2 { Some(y2 + 1) } else { None };
-
-        let x_floor = x.floor();
-        let x1 = x_floor as i32;
-        let x2 = if x - x_floor > 0.7 {
-            Some(x1 + 1)
-        } else if x - x_floor < 0.3 {
-            Some(x1 - 1)
-        } else {
-            None
-        };
-
-        let az = z;
-        let bz = z.floor() + 1.0;
-        let z = bz as i32;
-        if self.get_block(x1, y1, z).is_blocking() || self.get_block(x1, y2, z).is_blocking() {
-            return bz - az - 0.3;
-        }
-        if let Some(y3) = y3 {
-            if self.get_block(x1, y3, z).is_blocking() {
-                return bz - az - 0.3;
-            }
-            if let Some(x2) = x2 {
-                if self.get_block(x2, y3, z).is_blocking() {
-                    return bz - az - 0.3;
-                }
-            }
-        }
-        if let Some(x2) = x2 {
-            if self.get_block(x2, y1, z).is_blocking() || self.get_block(x2, y2, z).is_blocking() {
-                return bz - az - 0.3;
+    pub fn set_block_state_complex(&mut self, chunk_x: i32, chunk_y: i32, chunk_z: i32, block_x: u8, block_y: u8, block_z: u8, block_state_id: u32) {
+        let section_index = match self.section_index(chunk_y) {
+            Some(section_index) => section_index,
+            None => {
+                warn!("Block set outside of the world (section {})", chunk_y);
+                return;
             }
-        }
-
-        0.2
-    }
-
-    pub fn max_north_movement(&self, x: f64, y: f64, z: f64) -> f64 {
-        let y_floor = y.floor();
-        let y1 = y_floor as i32;
-        let y2 = y1 + 1;
-        let y3 = if y - y_floor > 0.2 { Some(y2 + 1) } else { None };
-
-        let x_floor = x.floor();
-        let x1 = x_floor as i32;
-        let x2 = if x - x_floor > 0.7 {
-            Some(x1 + 1)
-        } else if x - x_floor < 0.3 {
-            Some(x1 - 1)
-        } else {
-            None
         };
-
-        let az = z;
-        let bz = z.floor();
-        let z = bz as i32 - 1;
-        if self.get_block(x1, y1, z).is_blocking() || self.get_block(x1, y2, z).is_blocking() {
-            return az - bz - 0.3;
-        }
-        if let Some(y3) = y3 {
-            if self.get_block(x1, y3, z).is_blocking() {
-                return az - bz - 0.3;
-            }
-            if let Some(x2) = x2 {
-                if self.get_block(x2, y3, z).is_blocking() {
-                    return az - bz - 0.3;
-                }
-            }
-        }
-        if let Some(x2) = x2 {
-            if self.get_block(x2, y1, z).is_blocking() || self.get_block(x2, y2, z).is_blocking() {
-                return az - bz - 0.3;
-            }
-        }
-
-        0.2
-    }
-
-    pub fn max_fall(&self, x: f64, y: f64, z: f64) -> f64 {
-        if self.is_on_ground(x, y, z) {
-            return 0.0;
-        }
-        if self.is_on_ground(x, y - 1.0, z) {
-            return -1.0 + (y.ceil() - y);
-        }
-        if self.is_on_ground(x, y - 2.0, z) {
-            return -2.0 + (y.ceil() - y);
-        }
-
-        -2.0
-    }
-
-    pub fn set_block_state_complex(&mut self, chunk_x: i32, chunk_y: i32, chunk_z: i32, block_x: u8, block_y: u8, block_z: u8, block_state_id: u32) {
         let chunk_column = match self.chunk_columns.get_mut(&(chunk_x, chunk_z)) {
             Some(chunk_column) => chunk_column,
             None => {
@@ -314,7 +192,7 @@ impl Map {
             }
         };
 
-        let chunk_section = match chunk_column.get_mut(chunk_y as usize) {
+        let chunk_section = match chunk_column.get_mut(section_index) {
             Some(chunk_section) => chunk_section,
             None => {
                 warn!("Block set in a chunk that does not exist");
@@ -412,9 +290,11 @@ impl Map {
             }
 
             // Scan the chunk
-            for chunk_y in 0..16 {
+            let min_chunk_y = self.dimension.min_y.div_euclid(16);
+            for section_index in 0..self.dimension.section_count() {
+                let chunk_y = min_chunk_y + section_index as i32;
                 if let Some(chunk_column) = self.chunk_columns.get(&(chunk_x, chunk_z)) {
-                    if let Some(Some(chunk_section)) = chunk_column.get(chunk_y as usize) {
+                    if let Some(Some(chunk_section)) = chunk_column.get(section_index) {
                         if let Some(palette) = chunk_section.palette.as_ref() {
                             let mut searched_ids = Vec::new();
                             for contained_block_state in palette {
@@ -464,3 +344,41 @@ impl Map {
         results
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dimension_bounds() {
+        let dimension = DimensionType { min_y: -64, height: 384 };
+        assert_eq!(dimension.max_y(), 319);
+        assert_eq!(dimension.section_count(), 24);
+
+        let mut map = Map::with_dimension(dimension);
+        map.load_empty_chunk(0, 0);
+        map.set_block(1, -64, 1, Block::Stone);
+        map.set_block(1, 319, 1, Block::Stone);
+        assert_eq!(map.get_block(1, -64, 1), Block::Stone);
+        assert_eq!(map.get_block(1, 319, 1), Block::Stone);
+        // Outside of the world
+        map.set_block(1, 320, 1, Block::Stone);
+        assert_eq!(map.get_block(1, 320, 1), Block::Air);
+        assert_eq!(map.get_block(1, -65, 1), Block::Air);
+    }
+
+    #[test]
+    fn test_columns_are_resized() {
+        let mut map = Map::with_dimension(DimensionType { min_y: -64, height: 384 });
+        // A column of the default overworld height
+        map.insert_column(0, 0, (0..16).map(|_| None).collect());
+        map.set_block(2, 300, 2, Block::Stone);
+        assert_eq!(map.get_block(2, 300, 2), Block::Stone);
+
+        let mut map = Map::new();
+        map.insert_column(0, 0, (0..24).map(|_| None).collect());
+        map.set_block(2, 255, 2, Block::Stone);
+        assert_eq!(map.get_block(2, 255, 2), Block::Stone);
+        assert_eq!(map.chunk_columns[&(0, 0)].len(), 16);
+    }
+}
@@ -1,9 +1,13 @@
 use crate::*;
 
 mod dig_down;
+mod scheduler;
+mod swim;
 mod travel;
 mod wood_cutting;
 pub use dig_down::*;
+pub use scheduler::*;
+pub use swim::*;
 pub use travel::*;
 pub use wood_cutting::*;
 
@@ -16,4 +20,45 @@ pub enum MissionResult<T> {
 
 pub trait Mission<T>: Send {
     fn execute(&mut self, bot: &mut Bot, packets: &mut Vec<ServerboundPacket>) -> MissionResult<T>;
+
+    /// Called when the mission is cancelled before being done.
+    /// The bot has already stopped digging, closed its windows and stopped moving.
+    fn cleanup(&mut self, _bot: &mut Bot, _packets: &mut Vec<ServerboundPacket>) {}
+}
+
+impl<T, M: Mission<T> + ?Sized> Mission<T> for Box<M> {
+    fn execute(&mut self, bot: &mut Bot, packets: &mut Vec<ServerboundPacket>) -> MissionResult<T> {
+        (**self).execute(bot, packets)
+    }
+
+    fn cleanup(&mut self, bot: &mut Bot, packets: &mut Vec<ServerboundPacket>) {
+        (**self).cleanup(bot, packets)
+    }
+}
+
+/// Creates a mission from its name and arguments, like `travel -222 75 54`.
+/// Known missions are `dig_down <until_y>`, `travel <x> <y> <z> [max_work]`, `swim_to <x> <y> <z>` and `cut_trees <wood> <sapplings>`.
+pub fn parse_mission(spec: &str) -> Result<Box<dyn Mission<Result<String, String>>>, String> {
+    fn arg<T: std::str::FromStr>(args: &[&str], idx: usize, name: &str) -> Result<T, String> {
+        let value = args.get(idx).ok_or_else(|| format!("Missing argument {}", name))?;
+        value.parse().map_err(|_| format!("Invalid {}: {:?}", name, value))
+    }
+
+    let mut parts = spec.split_whitespace();
+    let name = parts.next().ok_or_else(|| "Empty mission".to_string())?;
+    let args: Vec<&str> = parts.collect();
+    match name.replace('-', "_").as_str() {
+        "dig_down" => Ok(Box::new(dig_down(arg(&args, 0, "until_y")?))),
+        "travel" => {
+            let destination = (arg(&args, 0, "x")?, arg(&args, 1, "y")?, arg(&args, 2, "z")?);
+            let maximum_work_allowed = match args.get(3) {
+                Some(_) => arg(&args, 3, "max_work")?,
+                None => 7500,
+            };
+            Ok(Box::new(travel(destination, maximum_work_allowed)))
+        }
+        "swim_to" => Ok(Box::new(swim_to((arg(&args, 0, "x")?, arg(&args, 1, "y")?, arg(&args, 2, "z")?)))),
+        "cut_trees" => Ok(Box::new(cut_trees(arg(&args, 0, "wood")?, arg(&args, 1, "sapplings")?))),
+        name => Err(format!("Unknown mission {:?}", name)),
+    }
 }
@@ -0,0 +1,449 @@
+use crate::*;
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
+
+pub type MissionId = u64;
+
+/// Ids are unique in the process so that missions can be numbered before reaching their scheduler
+static NEXT_MISSION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A mission with a higher priority interrupts the running one, which resumes once it is done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MissionPriority {
+    /// Runs only when nothing else has to be done
+    Background,
+    Normal,
+    High,
+    /// Survival matters, like fleeing or defending
+    Critical,
+}
+
+pub(crate) struct ScheduledMission {
+    id: MissionId,
+    name: String,
+    priority: MissionPriority,
+    mission: Box<dyn Mission<Result<String, String>>>,
+}
+
+/// A queued or running mission
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissionInfo {
+    pub id: MissionId,
+    pub name: String,
+    pub priority: MissionPriority,
+}
+
+/// A mission that is over
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissionRecord {
+    pub id: MissionId,
+    pub name: String,
+    pub priority: MissionPriority,
+    /// `Err` if the mission failed, was outdated or was cancelled
+    pub result: Result<String, String>,
+    pub finished_at: Instant,
+}
+
+/// Runs the missions of the bot one at a time, by priority.
+/// Missions of the same priority run in the order they were submitted.
+///
+/// Cancelling and pausing take effect at the next tick, since the bot has to stop what it was doing.
+pub struct MissionScheduler {
+    current: Option<ScheduledMission>,
+    /// Sorted by decreasing priority
+    pending: VecDeque<ScheduledMission>,
+    /// Missions to clean up at the next tick
+    cancelled: Vec<ScheduledMission>,
+    /// Whether the running mission was cancelled, in which case the bot has to stop what it was doing
+    current_cancelled: bool,
+    paused: bool,
+    /// Whether the bot has been stopped since the scheduler was paused
+    stopped: bool,
+    history: VecDeque<MissionRecord>,
+    /// How many finished missions are kept in the history
+    pub max_history: usize,
+}
+
+impl MissionScheduler {
+    pub fn new() -> MissionScheduler {
+        MissionScheduler {
+            current: None,
+            pending: VecDeque::new(),
+            cancelled: Vec::new(),
+            current_cancelled: false,
+            paused: false,
+            stopped: false,
+            history: VecDeque::new(),
+            max_history: 50,
+        }
+    }
+
+    /// Queues a mission. It starts at the next tick if it has a higher priority than the running one.
+    pub fn submit(&mut self, name: impl Into<String>, priority: MissionPriority, mission: Box<dyn Mission<Result<String, String>>>) -> MissionId {
+        let mission = ScheduledMission::new(name, priority, mission);
+        let id = mission.id;
+        self.submit_scheduled(mission);
+        id
+    }
+
+    /// Queues a mission numbered by [ScheduledMission::new]
+    pub(crate) fn submit_scheduled(&mut self, mission: ScheduledMission) {
+        debug!("Mission {} ({}) submitted with priority {:?}", mission.id, mission.name, mission.priority);
+        self.enqueue(mission);
+    }
+
+    /// Inserts after the missions of the same or higher priority
+    fn enqueue(&mut self, mission: ScheduledMission) {
+        let idx = self.pending.iter().position(|m| m.priority < mission.priority).unwrap_or(self.pending.len());
+        self.pending.insert(idx, mission);
+    }
+
+    /// Inserts before the missions of the same priority, so that an interrupted mission resumes first
+    fn enqueue_first(&mut self, mission: ScheduledMission) {
+        let idx = self.pending.iter().position(|m| m.priority <= mission.priority).unwrap_or(self.pending.len());
+        self.pending.insert(idx, mission);
+    }
+
+    pub fn current(&self) -> Option<MissionInfo> {
+        self.current.as_ref().map(ScheduledMission::info)
+    }
+
+    pub fn pending(&self) -> Vec<MissionInfo> {
+        self.pending.iter().map(ScheduledMission::info).collect()
+    }
+
+    /// The finished missions, most recent last
+    pub fn history(&self) -> &VecDeque<MissionRecord> {
+        &self.history
+    }
+
+    /// Whether there is no running or pending mission
+    pub fn is_idle(&self) -> bool {
+        self.current.is_none() && self.pending.is_empty()
+    }
+
+    /// Cancels a running or pending mission. Returns false if there is no such mission.
+    pub fn cancel(&mut self, id: MissionId) -> bool {
+        if self.current.as_ref().map(|m| m.id == id).unwrap_or(false) {
+            self.cancelled.extend(self.current.take());
+            self.current_cancelled = true;
+            return true;
+        }
+        match self.pending.iter().position(|m| m.id == id) {
+            Some(idx) => {
+                self.cancelled.extend(self.pending.remove(idx));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancels the running mission, if any. The next pending mission will start.
+    pub fn cancel_current(&mut self) -> Option<MissionId> {
+        let id = self.current.as_ref()?.id;
+        self.cancel(id);
+        Some(id)
+    }
+
+    /// Cancels the running mission and every pending one
+    pub fn cancel_all(&mut self) {
+        if let Some(current) = self.current.take() {
+            self.cancelled.push(current);
+            self.current_cancelled = true;
+        }
+        self.cancelled.extend(self.pending.drain(..));
+    }
+
+    /// Stops running missions until [MissionScheduler::resume] is called.
+    /// The running mission stays current and continues where it stopped.
+    pub fn pause(&mut self) {
+        if !self.paused {
+            info!("Missions paused");
+            self.paused = true;
+            self.stopped = false;
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if self.paused {
+            info!("Missions resumed");
+            self.paused = false;
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Makes the right mission current, interrupting the running one if a more important one is pending
+    fn select(&mut self, bot: &mut Bot, packets: &mut Vec<ServerboundPacket>) {
+        let preempt = match (&self.current, self.pending.front()) {
+            (Some(current), Some(next)) => next.priority > current.priority,
+            (None, Some(_)) => true,
+            (_, None) => false,
+        };
+        if preempt {
+            let next = self.pending.pop_front();
+            if let Some(interrupted) = self.current.take() {
+                info!("Mission {} ({}) interrupted", interrupted.id, interrupted.name);
+                bot.stop_actions(packets);
+                self.enqueue_first(interrupted);
+            }
+            self.current = next;
+            if let Some(current) = &self.current {
+                info!("Mission {} ({}) started", current.id, current.name);
+            }
+        }
+    }
+
+    fn record(&mut self, mission: ScheduledMission, result: Result<String, String>) {
+        self.history.push_back(MissionRecord {
+            id: mission.id,
+            name: mission.name,
+            priority: mission.priority,
+            result,
+            finished_at: Instant::now(),
+        });
+        while self.history.len() > self.max_history {
+            self.history.pop_front();
+        }
+    }
+
+    /// Runs one tick of the current mission
+    pub(crate) fn execute(&mut self, bot: &mut Bot, packets: &mut Vec<ServerboundPacket>) {
+        // Pending missions never acted, so only the running one leaves something to stop
+        if std::mem::take(&mut self.current_cancelled) {
+            bot.stop_actions(packets);
+        }
+        for mut mission in std::mem::take(&mut self.cancelled) {
+            info!("Mission {} ({}) cancelled", mission.id, mission.name);
+            mission.mission.cleanup(bot, packets);
+            self.record(mission, Err("Cancelled".to_string()));
+        }
+
+        if self.paused {
+            if !self.stopped {
+                bot.stop_actions(packets);
+                self.stopped = true;
+            }
+            return;
+        }
+
+        self.select(bot, packets);
+        let current = match self.current.as_mut() {
+            Some(current) => current,
+            None => return,
+        };
+        let result = match current.mission.execute(bot, packets) {
+            MissionResult::InProgress => return,
+            MissionResult::Done(Ok(message)) => {
+                info!("Mission {} ({}) success: {}", current.id, current.name, message);
+                Ok(message)
+            }
+            MissionResult::Done(Err(error)) => {
+                warn!("Mission {} ({}) failure: {}", current.id, current.name, error);
+                Err(error)
+            }
+            MissionResult::Outdated => {
+                error!("Mission {} ({}) is outdated", current.id, current.name);
+                Err("Outdated mission".to_string())
+            }
+        };
+        if let Some(mission) = self.current.take() {
+            self.record(mission, result);
+        }
+    }
+}
+
+impl ScheduledMission {
+    pub(crate) fn new(name: impl Into<String>, priority: MissionPriority, mission: Box<dyn Mission<Result<String, String>>>) -> ScheduledMission {
+        ScheduledMission {
+            id: NEXT_MISSION_ID.fetch_add(1, Ordering::Relaxed),
+            name: name.into(),
+            priority,
+            mission,
+        }
+    }
+
+    pub(crate) fn id(&self) -> MissionId {
+        self.id
+    }
+
+    fn info(&self) -> MissionInfo {
+        MissionInfo {
+            id: self.id,
+            name: self.name.clone(),
+            priority: self.priority,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::PacketSender;
+    use std::sync::{Arc, Mutex};
+
+    /// Logs its ticks and finishes after `ticks` ticks
+    struct Counting {
+        name: &'static str,
+        ticks: usize,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Mission<Result<String, String>> for Counting {
+        fn execute(&mut self, _bot: &mut Bot, _packets: &mut Vec<ServerboundPacket>) -> MissionResult<Result<String, String>> {
+            self.log.lock().unwrap().push(self.name.to_string());
+            self.ticks = self.ticks.saturating_sub(1);
+            match self.ticks {
+                0 => MissionResult::Done(Ok(self.name.to_string())),
+                _ => MissionResult::InProgress,
+            }
+        }
+
+        fn cleanup(&mut self, _bot: &mut Bot, _packets: &mut Vec<ServerboundPacket>) {
+            self.log.lock().unwrap().push(format!("cleanup {}", self.name));
+        }
+    }
+
+    struct Test {
+        bot: Bot,
+        scheduler: MissionScheduler,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Test {
+        fn new() -> Test {
+            let (sender, _receiver) = PacketSender::channel();
+            Test {
+                bot: Bot::new(BotConfig::new("localhost", 25565, "bot"), sender),
+                scheduler: MissionScheduler::new(),
+                log: Arc::new(Mutex::new(Vec::new())),
+            }
+        }
+
+        fn submit(&mut self, name: &'static str, priority: MissionPriority, ticks: usize) -> MissionId {
+            let log = Arc::clone(&self.log);
+            self.scheduler.submit(name, priority, Box::new(Counting { name, ticks, log }))
+        }
+
+        fn tick(&mut self) {
+            let mut packets = Vec::new();
+            self.scheduler.execute(&mut self.bot, &mut packets);
+        }
+
+        fn current(&self) -> Option<String> {
+            self.scheduler.current().map(|mission| mission.name)
+        }
+
+        fn take_log(&self) -> Vec<String> {
+            std::mem::take(&mut *self.log.lock().unwrap())
+        }
+    }
+
+    #[test]
+    fn test_priority_order() {
+        let mut test = Test::new();
+        test.submit("background", MissionPriority::Background, 1);
+        test.submit("first", MissionPriority::Normal, 1);
+        test.submit("second", MissionPriority::Normal, 1);
+        test.submit("high", MissionPriority::High, 1);
+        for _ in 0..4 {
+            test.tick();
+        }
+        assert_eq!(test.take_log(), vec!["high", "first", "second", "background"]);
+        assert!(test.scheduler.is_idle());
+        assert_eq!(test.scheduler.history().len(), 4);
+    }
+
+    #[test]
+    fn test_preemption_and_resume() {
+        let mut test = Test::new();
+        let normal = test.submit("normal", MissionPriority::Normal, 3);
+        test.tick();
+        assert_eq!(test.current().as_deref(), Some("normal"));
+
+        // A mission of the same priority waits
+        test.submit("other", MissionPriority::Normal, 1);
+        test.tick();
+        assert_eq!(test.current().as_deref(), Some("normal"));
+
+        // A more important one interrupts it
+        test.submit("critical", MissionPriority::Critical, 2);
+        test.tick();
+        assert_eq!(test.current().as_deref(), Some("critical"));
+        assert_eq!(test.scheduler.pending()[0].id, normal);
+
+        // The interrupted mission resumes before the one of the same priority that was queued after it
+        test.tick();
+        test.tick();
+        assert_eq!(test.current(), None);
+        test.tick();
+        assert_eq!(test.take_log(), vec!["normal", "normal", "critical", "critical", "normal", "other"]);
+        let results: Vec<_> = test.scheduler.history().iter().map(|record| record.result.clone()).collect();
+        assert_eq!(results, vec![Ok("critical".to_string()), Ok("normal".to_string()), Ok("other".to_string())]);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let mut test = Test::new();
+        let running = test.submit("running", MissionPriority::Normal, 10);
+        let pending = test.submit("pending", MissionPriority::Normal, 10);
+        let next = test.submit("next", MissionPriority::Normal, 1);
+        test.tick();
+        assert!(!test.scheduler.cancel(12345));
+
+        assert_eq!(test.scheduler.cancel_current(), Some(running));
+        assert_eq!(test.current(), None);
+        assert!(test.scheduler.cancel(pending));
+        assert_eq!(test.scheduler.pending().len(), 1);
+
+        // Cancelled missions are cleaned up at the next tick, then the next mission starts
+        test.tick();
+        assert_eq!(test.take_log(), vec!["running", "cleanup running", "cleanup pending", "next"]);
+        let history: Vec<_> = test.scheduler.history().iter().map(|record| (record.id, record.result.clone())).collect();
+        assert_eq!(
+            history,
+            vec![(running, Err("Cancelled".to_string())), (pending, Err("Cancelled".to_string())), (next, Ok("next".to_string()))]
+        );
+        assert!(!test.scheduler.cancel(running));
+    }
+
+    #[test]
+    fn test_cancel_all() {
+        let mut test = Test::new();
+        test.submit("running", MissionPriority::Normal, 10);
+        test.submit("pending", MissionPriority::Normal, 10);
+        test.tick();
+        test.scheduler.cancel_all();
+        assert!(test.scheduler.is_idle());
+        test.tick();
+        assert_eq!(test.take_log(), vec!["running", "cleanup running", "cleanup pending"]);
+    }
+
+    #[test]
+    fn test_pause() {
+        let mut test = Test::new();
+        test.submit("mission", MissionPriority::Normal, 2);
+        test.tick();
+        test.scheduler.pause();
+        assert!(test.scheduler.is_paused());
+
+        // Nothing runs nor starts while paused, but the mission stays current
+        test.submit("critical", MissionPriority::Critical, 1);
+        test.tick();
+        test.tick();
+        assert_eq!(test.take_log(), vec!["mission"]);
+        assert_eq!(test.current().as_deref(), Some("mission"));
+
+        test.scheduler.resume();
+        assert!(!test.scheduler.is_paused());
+        test.tick();
+        test.tick();
+        assert_eq!(test.take_log(), vec!["critical", "mission"]);
+        assert!(test.scheduler.is_idle());
+    }
+}
@@ -0,0 +1,27 @@
+use crate::*;
+use minecraft_bot_macros::tick_distributed;
+
+/// Swims in a straight line to a point, going up and down in the water.
+/// No path is searched, so the destination has to be reachable without going around obstacles.
+#[tick_distributed]
+pub fn swim_to(destination: (f64, f64, f64), mt_bot: &mut Bot, mt_packets: &mut Vec<ServerboundPacket>) -> Result<String, String> {
+    let mut stucked_detector: usize = 0;
+
+    'mt_swim: loop {
+        let distance: f64 = bot.physics.swim_towards(bot.position.as_mut().unwrap(), destination);
+        if distance < 0.5 {
+            return Ok("Swim finished".to_string());
+        }
+
+        if bot.physics.horizontal_collision {
+            stucked_detector += 1;
+        } else {
+            stucked_detector = 0;
+        }
+        if stucked_detector > 100 {
+            return Err("Bot is stucked while swimming".to_string());
+        }
+    }
+
+    Ok("Swim finished".to_string())
+}
@@ -1,6 +1,5 @@
 use crate::*;
 use minecraft_bot_macros::tick_distributed;
-use std::cmp::Ordering;
 
 #[tick_distributed]
 pub fn travel(destination: (i32, i32, i32), maximum_work_allowed: usize, mt_bot: &mut Bot, mt_packets: &mut Vec<ServerboundPacket>) -> Result<String, String> {
@@ -23,14 +22,12 @@ pub fn travel(destination: (i32, i32, i32), maximum_work_allowed: usize, mt_bot:
     let mut stucked_detector: usize = 0;
 
     'mt_travel: loop {
-        let (mut x, y, mut z): (f64, f64, f64) = (
+        let (x, y, z): (f64, f64, f64) = (
             bot.position.as_ref().unwrap().x,
             bot.position.as_ref().unwrap().y,
             bot.position.as_ref().unwrap().z,
         );
         let (bx, by, bz): (i32, i32, i32) = (x.floor() as i32, y.floor() as i32, z.floor() as i32);
-        let on_ground: bool = bot.map.is_on_ground(x, y, z);
-        let mut jump: bool = false;
 
         if stucked_detector > 100 {
             warn!("Bot is stucked while traveling. Recalculating...");
@@ -64,63 +61,16 @@ pub fn travel(destination: (i32, i32, i32), maximum_work_allowed: usize, mt_bot:
             continue 'mt_travel;
         }
 
-        if ny > by && on_ground {
-            jump = true;
+        let (target_x, target_z): (f64, f64) = (nx as f64 + 0.5, nz as f64 + 0.5);
+        let distance: f64 = ((target_x - x).powi(2) + (target_z - z).powi(2)).sqrt();
+        if distance > 0.05 {
+            bot.position.as_mut().unwrap().yaw = yaw_towards(x, z, target_x, target_z);
+            // Slow down near the center of the block to avoid overshooting it
+            bot.physics.input.forward = (distance * 4.0).min(1.0);
         }
-
-        let mut movement_required: f64 = (nx as f64 + 0.5 - x).abs();
-        if movement_required > 0.2 {
-            movement_required = 0.2;
-        }
-        match (nx as f64 + 0.5).partial_cmp(&x) {
-            Some(Ordering::Less) => {
-                let max = bot.map.max_west_movement(x, y, z);
-                x -= if max > movement_required { movement_required } else { max };
-            }
-            Some(Ordering::Greater) => {
-                let max = bot.map.max_east_movement(x, y, z);
-                x += if max > movement_required { movement_required } else { max };
-            }
-            _ => {}
-        }
-
-        let mut movement_required: f64 = (nz as f64 + 0.5 - z).abs();
-        if movement_required > 0.2 {
-            movement_required = 0.2;
-        }
-        match (nz as f64 + 0.5).partial_cmp(&z) {
-            Some(Ordering::Less) => {
-                let max = bot.map.max_north_movement(x, y, z);
-                z -= if max > movement_required { movement_required } else { max };
-            }
-            Some(Ordering::Greater) => {
-                let max = bot.map.max_south_movement(x, y, z);
-                z += if max > movement_required { movement_required } else { max };
-            }
-            _ => {}
-        }
-
-        let yaw: f32 = match ((nx as f64 + 0.5).partial_cmp(&x), (nz as f64 + 0.5).partial_cmp(&z)) {
-            (None, None) | (Some(Ordering::Equal), Some(Ordering::Equal)) | (None, Some(Ordering::Equal)) | (Some(Ordering::Equal), None) => {
-                bot.position.as_ref().unwrap().yaw
-            }
-            (None, Some(Ordering::Less)) | (Some(Ordering::Equal), Some(Ordering::Less)) => 180.0,
-            (None, Some(Ordering::Greater)) | (Some(Ordering::Equal), Some(Ordering::Greater)) => 0.0,
-            (Some(Ordering::Less), None) | (Some(Ordering::Less), Some(Ordering::Equal)) => 90.0,
-            (Some(Ordering::Greater), None) | (Some(Ordering::Greater), Some(Ordering::Equal)) => 270.0,
-            (Some(Ordering::Less), Some(Ordering::Less)) => 135.0,
-            (Some(Ordering::Less), Some(Ordering::Greater)) => 45.0,
-            (Some(Ordering::Greater), Some(Ordering::Less)) => 225.0,
-            (Some(Ordering::Greater), Some(Ordering::Greater)) => 315.0,
-        };
-        packets.push(ServerboundPacket::PlayerRotation { yaw, pitch: 0.0, on_ground });
+        bot.physics.input.jump = ny > by && (bot.physics.on_ground || bot.physics.on_climbable);
 
         stucked_detector += 1;
-        bot.position.as_mut().unwrap().x = x;
-        bot.position.as_mut().unwrap().z = z;
-        if jump {
-            bot.vertical_speed = 0.4;
-        }
     }
 
     Ok("Travel finished".to_string())
@@ -0,0 +1,134 @@
+use log::*;
+use num_bigint::BigInt;
+use sha1::{Digest, Sha1};
+
+/// Validates the session with a session server before the encryption handshake is completed.
+/// The server will check that the player has joined with the same hash.
+pub trait SessionAuthenticator: Send + Sync {
+    fn join(&self, username: &str, server_hash: &str) -> Result<(), String>;
+}
+
+/// Does not contact any session server.
+/// Only offline-mode servers (or servers that don't verify sessions) will accept the connection.
+pub struct OfflineAuthenticator;
+
+impl SessionAuthenticator for OfflineAuthenticator {
+    fn join(&self, username: &str, _server_hash: &str) -> Result<(), String> {
+        warn!("Server requested encryption but {} has no session authenticator. The server will probably reject it.", username);
+        Ok(())
+    }
+}
+
+/// Authenticates against a Yggdrasil-compatible session server.
+pub struct MojangAuthenticator {
+    pub session_server: String,
+    pub access_token: String,
+    pub selected_profile: String,
+}
+
+impl MojangAuthenticator {
+    pub fn new(access_token: String, selected_profile: String) -> MojangAuthenticator {
+        MojangAuthenticator {
+            session_server: "https://sessionserver.mojang.com".to_string(),
+            access_token,
+            selected_profile,
+        }
+    }
+
+    /// Use another session server (a local stand-in for example).
+    pub fn with_session_server(mut self, session_server: String) -> MojangAuthenticator {
+        self.session_server = session_server;
+        self
+    }
+}
+
+impl SessionAuthenticator for MojangAuthenticator {
+    fn join(&self, username: &str, server_hash: &str) -> Result<(), String> {
+        debug!("Joining session of {} (server hash: {})", username, server_hash);
+        let response = ureq::post(&format!("{}/session/minecraft/join", self.session_server.trim_end_matches('/')))
+            .send_json(ureq::json!({
+                "accessToken": self.access_token,
+                "selectedProfile": self.selected_profile,
+                "serverId": server_hash,
+            }))
+            .map_err(|e| format!("Session server refused to join: {}", e))?;
+
+        match response.status() {
+            200..=299 => Ok(()),
+            status => Err(format!("Session server answered with status {}", status)),
+        }
+    }
+}
+
+/// Computes the Minecraft-flavored SHA-1 hex digest (a signed big integer in base 16).
+pub fn server_hash(server_id: &str, shared_secret: &[u8], public_key: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(server_id.as_bytes());
+    hasher.update(shared_secret);
+    hasher.update(public_key);
+    let hash = hasher.finalize();
+
+    BigInt::from_signed_bytes_be(&hash).to_str_radix(16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+    };
+
+    #[test]
+    fn test_server_hash() {
+        // Known digests, including negative ones
+        assert_eq!(server_hash("Notch", &[], &[]), "4ed1f46bbe04bc756bcb17c0c7ce3e4632f06a48");
+        assert_eq!(server_hash("jeb_", &[], &[]), "-7c9d5b0044c130109a5d7b5fb5c317c02b4e28c1");
+        assert_eq!(server_hash("simon", &[], &[]), "88e16a1019277b15d58faf0541e11910eb756f6");
+        assert_eq!(server_hash("No", b"tc", b"h"), server_hash("Notch", &[], &[]));
+    }
+
+    /// Answers one HTTP request with the given status line and returns the request
+    fn session_server(status: &'static str) -> (String, std::thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 4096];
+            // Read until the whole JSON body is received
+            while !request.ends_with(b"}") {
+                let read = stream.read(&mut buf).unwrap();
+                if read == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..read]);
+            }
+            write!(stream, "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn test_mojang_authenticator() {
+        let (url, server) = session_server("204 No Content");
+        let authenticator = MojangAuthenticator::new("token".to_string(), "profile".to_string()).with_session_server(url);
+        authenticator.join("bot", "-1234abcd").unwrap();
+
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /session/minecraft/join "));
+        let body: serde_json::Value = serde_json::from_str(&request[request.find('{').unwrap()..]).unwrap();
+        assert_eq!(body["accessToken"], "token");
+        assert_eq!(body["selectedProfile"], "profile");
+        assert_eq!(body["serverId"], "-1234abcd");
+    }
+
+    #[test]
+    fn test_mojang_authenticator_rejected() {
+        let (url, server) = session_server("403 Forbidden");
+        let authenticator = MojangAuthenticator::new("token".to_string(), "profile".to_string()).with_session_server(url);
+        assert!(authenticator.join("bot", "hash").is_err());
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,118 @@
+use aes::Aes128;
+use cfb8::{
+    cipher::{AsyncStreamCipher, NewCipher},
+    Cfb8,
+};
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+};
+
+pub(crate) type AesCfb8 = Cfb8<Aes128>;
+
+/// A TCP stream that can be switched to AES/CFB8 encryption once the login handshake is done.
+/// Each direction must have its own [CipherStream] since the cipher state is updated with every byte.
+pub struct CipherStream {
+    stream: TcpStream,
+    cipher: Option<AesCfb8>,
+}
+
+impl CipherStream {
+    pub fn new(stream: TcpStream) -> CipherStream {
+        CipherStream { stream, cipher: None }
+    }
+
+    /// Minecraft uses the shared secret both as the key and the initial vector.
+    pub fn enable_encryption(&mut self, shared_secret: &[u8; 16]) {
+        self.cipher = Some(AesCfb8::new_from_slices(shared_secret, shared_secret).expect("Shared secret has an invalid length"));
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    pub fn get_ref(&self) -> &TcpStream {
+        &self.stream
+    }
+
+    pub(crate) fn into_parts(self) -> (TcpStream, Option<AesCfb8>) {
+        (self.stream, self.cipher)
+    }
+}
+
+impl Read for CipherStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.stream.read(buf)?;
+        if let Some(cipher) = self.cipher.as_mut() {
+            cipher.decrypt(&mut buf[..read]);
+        }
+        Ok(read)
+    }
+}
+
+impl Write for CipherStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.cipher.as_mut() {
+            Some(cipher) => {
+                let mut encrypted = buf.to_vec();
+                cipher.encrypt(&mut encrypted);
+                self.stream.write_all(&encrypted)?;
+                Ok(buf.len())
+            }
+            None => self.stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn stream_pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        (client, server)
+    }
+
+    #[test]
+    fn test_encrypted_round_trip() {
+        let (client, server) = stream_pair();
+        let secret = [7; 16];
+        let mut writer = CipherStream::new(client);
+        let mut reader = CipherStream::new(server.try_clone().unwrap());
+        let mut raw = server;
+        writer.enable_encryption(&secret);
+        reader.enable_encryption(&secret);
+        assert!(writer.is_encrypted());
+
+        writer.write_all(b"hello").unwrap();
+        writer.write_all(b" world").unwrap();
+        let mut received = [0; 11];
+        reader.read_exact(&mut received).unwrap();
+        assert_eq!(&received, b"hello world");
+
+        // Nothing is sent in clear text once encryption is enabled
+        writer.write_all(b"secret").unwrap();
+        let mut on_the_wire = [0; 6];
+        raw.read_exact(&mut on_the_wire).unwrap();
+        assert_ne!(&on_the_wire, b"secret");
+    }
+
+    #[test]
+    fn test_plain_until_enabled() {
+        let (client, server) = stream_pair();
+        let mut writer = CipherStream::new(client);
+        let mut reader = CipherStream::new(server);
+        assert!(!writer.is_encrypted());
+        writer.write_all(b"plain").unwrap();
+        let mut received = [0; 5];
+        reader.read_exact(&mut received).unwrap();
+        assert_eq!(&received, b"plain");
+    }
+}
@@ -0,0 +1,373 @@
+use log::*;
+use minecraft_protocol::{network::*, *};
+use rand::RngCore;
+use rsa::{pkcs8::FromPublicKey, PaddingScheme, PublicKey, RsaPublicKey};
+use std::{
+    net::Shutdown,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+mod auth;
+mod encryption;
+mod plugin_channels;
+mod proxy;
+mod reconnect;
+mod recording;
+mod scheduler;
+mod stats;
+mod status;
+#[cfg(feature = "async")]
+mod tokio_transport;
+pub use auth::*;
+pub use encryption::*;
+pub use plugin_channels::*;
+pub use proxy::*;
+pub use reconnect::*;
+pub use recording::*;
+pub use scheduler::*;
+pub use stats::*;
+pub use status::*;
+#[cfg(feature = "async")]
+pub use tokio_transport::*;
+
+/// The protocol version of Minecraft 1.17.1, the only one the packets are implemented for.
+pub const PROTOCOL_VERSION: i32 = 756;
+
+/// The largest packet allowed by the protocol, the largest length encoded on 3 bytes
+pub(crate) const MAX_PACKET_LENGTH: i32 = 2_097_151;
+
+/// Reads a VarInt at the beginning of `data`, returning its value and its size
+pub(crate) fn decode_varint(data: &[u8]) -> Option<(i32, usize)> {
+    let mut value: i32 = 0;
+    for (idx, byte) in data.iter().enumerate().take(5) {
+        value |= ((byte & 0x7F) as i32) << (7 * idx);
+        if byte & 0x80 == 0 {
+            return Some((value, idx + 1));
+        }
+    }
+    None
+}
+
+#[derive(Clone)]
+pub struct ConnectionOptions {
+    pub authenticator: Arc<dyn SessionAuthenticator>,
+    /// The protocol version announced in the handshake.
+    /// When `None`, the server is pinged first and the connection fails if it does not run [PROTOCOL_VERSION].
+    pub protocol_version: Option<i32>,
+    /// Records every play packet of the connection.
+    pub recorder: Option<Arc<Mutex<PacketRecorder>>>,
+    /// The proxy to tunnel the connection through, if any.
+    pub proxy: Option<Proxy>,
+    /// Handlers for login and play plugin messages.
+    pub plugin_channels: Arc<PluginChannels>,
+    /// Rate limits of the outgoing packets, by priority class.
+    pub rate_limits: RateLimits,
+}
+
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        ConnectionOptions {
+            authenticator: Arc::new(OfflineAuthenticator),
+            protocol_version: Some(PROTOCOL_VERSION),
+            recorder: None,
+            proxy: None,
+            plugin_channels: Arc::new(PluginChannels::default()),
+            rate_limits: RateLimits::default(),
+        }
+    }
+}
+
+/// Why a connection is (or is not) usable anymore.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionState {
+    Connected,
+    /// The connection was closed gracefully, by the server (with a reason) or by the bot.
+    Disconnected(String),
+    /// The connection was lost because of an error.
+    Errored(String),
+}
+
+/// Events sent by the network threads to the bot.
+/// The first [ConnectionEvent::Closed] event tells why the connection ended, anything received after it should be ignored.
+#[derive(Debug)]
+pub enum ConnectionEvent {
+    Packet(Vec<u8>),
+    Closed(ConnectionState),
+}
+
+fn shutdown(stream: &CipherStream) {
+    // The socket may already be closed
+    let _ = stream.get_ref().shutdown(Shutdown::Both);
+}
+
+pub(crate) fn record(recorder: &Option<Arc<Mutex<PacketRecorder>>>, direction: PacketDirection, packet: &[u8]) {
+    if let Some(recorder) = recorder {
+        if let Err(e) = recorder.lock().unwrap().record(direction, packet) {
+            warn!("Failed to record packet: {}", e);
+        }
+    }
+}
+
+fn receive_packets(
+    hidden_sender: mpsc::Sender<ConnectionEvent>,
+    mut stream: CipherStream,
+    compression: Option<u32>,
+    running: Arc<AtomicBool>,
+    recorder: Option<Arc<Mutex<PacketRecorder>>>,
+    stats: Arc<Mutex<NetworkStats>>,
+) {
+    loop {
+        let packet = match read_packet(&mut stream, compression, None) {
+            Ok(packet) => packet,
+            Err(e) => {
+                let state = match running.swap(false, Ordering::SeqCst) {
+                    true => ConnectionState::Errored(format!("Failed to read packet: {:?}", e)),
+                    false => ConnectionState::Disconnected("Connection closed by the client".to_string()),
+                };
+                let _ = hidden_sender.send(ConnectionEvent::Closed(state));
+                break;
+            }
+        };
+        record(&recorder, PacketDirection::Clientbound, &packet);
+        stats.lock().unwrap().record_received(&packet);
+        if hidden_sender.send(ConnectionEvent::Packet(packet)).is_err() {
+            // The bot is not listening anymore
+            running.store(false, Ordering::SeqCst);
+            break;
+        }
+    }
+    shutdown(&stream);
+    trace!("Receiving thread stopped");
+}
+
+fn send_packets(
+    hidden_receiver: mpsc::Receiver<(PacketPriority, Vec<u8>)>,
+    mut stream: CipherStream,
+    compression: Option<u32>,
+    running: Arc<AtomicBool>,
+    events: mpsc::Sender<ConnectionEvent>,
+    recorder: Option<Arc<Mutex<PacketRecorder>>>,
+    stats: Arc<Mutex<NetworkStats>>,
+    mut scheduler: PacketScheduler,
+) {
+    'sending: while running.load(Ordering::SeqCst) {
+        // Wait for a new packet, or until a queued packet is allowed by the rate limits
+        let timeout = scheduler.next_ready_in().unwrap_or(Duration::from_millis(100)).min(Duration::from_millis(100));
+        match hidden_receiver.recv_timeout(timeout) {
+            Ok((priority, packet)) => scheduler.push(priority, packet),
+            Err(mpsc::RecvTimeoutError::Timeout) => (),
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                // Every sender has been dropped so the bot is done with this connection
+                running.store(false, Ordering::SeqCst);
+                break;
+            }
+        }
+        while let Ok((priority, packet)) = hidden_receiver.try_recv() {
+            scheduler.push(priority, packet);
+        }
+
+        while let Some(packet) = scheduler.pop_ready(Instant::now()) {
+            trace!("Sending {:?}", packet);
+            record(&recorder, PacketDirection::Serverbound, &packet);
+            stats.lock().unwrap().record_sent(&packet);
+            if let Err(e) = send_packet(&mut stream, packet, compression, None) {
+                if running.swap(false, Ordering::SeqCst) {
+                    let _ = events.send(ConnectionEvent::Closed(ConnectionState::Errored(format!("Failed to send packet: {:?}", e))));
+                }
+                break 'sending;
+            }
+        }
+    }
+    shutdown(&stream);
+    trace!("Sending thread stopped");
+}
+
+/// Answers an encryption request and returns the shared secret to use for both directions.
+fn encryption_response(
+    writer: &mut CipherStream,
+    username: &str,
+    server_id: &str,
+    public_key: &[u8],
+    verify_token: &[u8],
+    authenticator: &dyn SessionAuthenticator,
+) -> Result<[u8; 16], String> {
+    let mut rng = rand::thread_rng();
+    let mut shared_secret = [0; 16];
+    rng.fill_bytes(&mut shared_secret);
+
+    let hash = server_hash(server_id, &shared_secret, public_key);
+    authenticator.join(username, &hash)?;
+
+    let public_key = RsaPublicKey::from_public_key_der(public_key).map_err(|e| format!("Invalid server public key: {}", e))?;
+    let encrypted_shared_secret = public_key
+        .encrypt(&mut rng, PaddingScheme::new_pkcs1v15_encrypt(), &shared_secret)
+        .map_err(|e| format!("Failed to encrypt shared secret: {}", e))?;
+    let encrypted_verify_token = public_key
+        .encrypt(&mut rng, PaddingScheme::new_pkcs1v15_encrypt(), verify_token)
+        .map_err(|e| format!("Failed to encrypt verify token: {}", e))?;
+
+    send_packet(
+        writer,
+        minecraft_protocol::packets::login::ServerboundPacket::EncryptionResponse {
+            shared_secret: encrypted_shared_secret.into(),
+            verify_token: encrypted_verify_token.into(),
+        }
+        .serialize_minecraft_packet()
+        .unwrap(),
+        None,
+        None,
+    )
+    .map_err(|e| format!("Failed to send encryption response: {:?}", e))?;
+
+    Ok(shared_secret)
+}
+
+/// A connection that went through the login state and is ready for the play state.
+pub(crate) struct LoggedInConnection {
+    pub(crate) reader: CipherStream,
+    pub(crate) writer: CipherStream,
+    pub(crate) compression: Option<u32>,
+}
+
+/// Performs the handshake and the login sequence (blocking).
+pub(crate) fn login(addr: &str, port: u16, username: &str, options: &ConnectionOptions) -> Result<LoggedInConnection, ConnectionState> {
+    let protocol_version = match options.protocol_version {
+        Some(protocol_version) => protocol_version,
+        None => {
+            let status = ping_through(addr, port, options.proxy.as_ref()).map_err(|e| ConnectionState::Errored(format!("Failed to ping server: {}", e)))?;
+            // Only the packets of one protocol are implemented, so there is no point in trying another one
+            if status.version.protocol != PROTOCOL_VERSION {
+                return Err(ConnectionState::Errored(format!(
+                    "Server runs {} (protocol {}) but only protocol {} is supported",
+                    status.version.name, status.version.protocol, PROTOCOL_VERSION
+                )));
+            }
+            status.version.protocol
+        }
+    };
+
+    let stream = open_stream(addr, port, options.proxy.as_ref()).map_err(ConnectionState::Errored)?;
+    let mut reader = CipherStream::new(stream.try_clone().map_err(|e| ConnectionState::Errored(format!("Failed to clone stream: {}", e)))?);
+    let mut writer = CipherStream::new(stream);
+
+    send_packet(
+        &mut writer,
+        minecraft_protocol::packets::handshake::ServerboundPacket::Hello {
+            protocol_version: protocol_version.into(),
+            server_address: addr,
+            server_port: port,
+            next_state: minecraft_protocol::packets::ConnectionState::Login,
+        }
+        .serialize_minecraft_packet()
+        .unwrap(),
+        None,
+        None,
+    )
+    .map_err(|e| ConnectionState::Errored(format!("Failed to send handshake: {:?}", e)))?;
+
+    send_packet(
+        &mut writer,
+        minecraft_protocol::packets::login::ServerboundPacket::LoginStart { username }
+            .serialize_minecraft_packet()
+            .unwrap(),
+        None,
+        None,
+    )
+    .map_err(|e| ConnectionState::Errored(format!("Failed to send login start: {:?}", e)))?;
+
+    // The server may enable encryption and compression before accepting the login.
+    // Every packet following EncryptionResponse is encrypted and every packet following SetCompression is compressed.
+    let mut compression: Option<u32> = None;
+    loop {
+        let response = read_packet(&mut reader, compression, None).map_err(|e| ConnectionState::Errored(format!("Failed to read login packet: {:?}", e)))?;
+        let response_packet = minecraft_protocol::packets::login::ClientboundPacket::deserialize_uncompressed_minecraft_packet(&response)
+            .map_err(|e| ConnectionState::Errored(format!("Failed to parse login packet: {:?}", e)))?;
+
+        match response_packet {
+            minecraft_protocol::packets::login::ClientboundPacket::EncryptionRequest {
+                server_id,
+                public_key,
+                verify_token,
+            } => {
+                let shared_secret = encryption_response(
+                    &mut writer,
+                    username,
+                    server_id,
+                    &public_key.items,
+                    &verify_token.items,
+                    options.authenticator.as_ref(),
+                )
+                .map_err(|e| ConnectionState::Errored(format!("Encryption failed: {}", e)))?;
+                reader.enable_encryption(&shared_secret);
+                writer.enable_encryption(&shared_secret);
+                debug!("Encryption enabled");
+            }
+            minecraft_protocol::packets::login::ClientboundPacket::SetCompression { threshold } => {
+                // A negative threshold disables compression
+                compression = if threshold.0 >= 0 { Some(threshold.0 as u32) } else { None };
+                debug!("Compression threshold set to {:?}", compression);
+            }
+            minecraft_protocol::packets::login::ClientboundPacket::LoginPluginRequest { message_id, channel, data } => {
+                let response = options.plugin_channels.handle_login_request(channel, data.data);
+                send_packet(
+                    &mut writer,
+                    minecraft_protocol::packets::login::ServerboundPacket::LoginPluginResponse {
+                        message_id,
+                        data: response.as_ref().map(|data| minecraft_protocol::packets::RawBytes { data }),
+                    }
+                    .serialize_minecraft_packet()
+                    .unwrap(),
+                    compression,
+                    None,
+                )
+                .map_err(|e| ConnectionState::Errored(format!("Failed to send login plugin response: {:?}", e)))?;
+            }
+            minecraft_protocol::packets::login::ClientboundPacket::LoginSuccess { .. } => break,
+            minecraft_protocol::packets::login::ClientboundPacket::Disconnect { reason } => {
+                shutdown(&writer);
+                return Err(ConnectionState::Disconnected(crate::chat::plain_text(reason)));
+            }
+            response_packet => {
+                shutdown(&writer);
+                return Err(ConnectionState::Errored(format!("Unexpected login packet {:?}", response_packet)));
+            }
+        }
+    }
+
+    Ok(LoggedInConnection { reader, writer, compression })
+}
+
+/// Logs in and starts the network threads.
+/// The counters of the connection are written to `stats`.
+pub fn connect(
+    addr: &str,
+    port: u16,
+    username: &str,
+    options: &ConnectionOptions,
+    stats: Arc<Mutex<NetworkStats>>,
+) -> Result<(mpsc::Receiver<ConnectionEvent>, PacketSender), ConnectionState> {
+    let LoggedInConnection { reader, writer, compression } = login(addr, port, username, options)?;
+
+    let (hidden_sender, receiver) = mpsc::channel::<ConnectionEvent>();
+    let (sender, hidden_receiver) = PacketSender::channel();
+    let running = sender.running();
+    let running2 = Arc::clone(&running);
+    let events = hidden_sender.clone();
+    let recorder = options.recorder.clone();
+    let recorder2 = options.recorder.clone();
+    let stats2 = Arc::clone(&stats);
+    let scheduler = PacketScheduler::new(&options.rate_limits);
+
+    std::thread::spawn(move || {
+        receive_packets(hidden_sender, reader, compression, running, recorder, stats);
+    });
+    std::thread::spawn(move || {
+        send_packets(hidden_receiver, writer, compression, running2, events, recorder2, stats2, scheduler);
+    });
+
+    Ok((receiver, sender))
+}
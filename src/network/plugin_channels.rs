@@ -0,0 +1,133 @@
+use log::*;
+use std::collections::BTreeMap;
+
+/// Handles the data of a plugin message and optionally answers on the same channel.
+pub type PluginMessageHandler = Box<dyn Fn(&[u8]) -> Option<Vec<u8>> + Send + Sync>;
+
+/// Encodes a string like the protocol does (VarInt length prefix).
+pub fn encode_plugin_string(value: &str) -> Vec<u8> {
+    let mut data = Vec::with_capacity(value.len() + 5);
+    let mut len = value.len() as u32;
+    loop {
+        if len & !0x7F == 0 {
+            data.push(len as u8);
+            break;
+        }
+        data.push((len & 0x7F) as u8 | 0x80);
+        len >>= 7;
+    }
+    data.extend_from_slice(value.as_bytes());
+    data
+}
+
+fn decode_plugin_string(data: &[u8]) -> Option<String> {
+    let (len, offset) = super::decode_varint(data)?;
+    let end = offset.checked_add(usize::try_from(len).ok()?)?;
+    let bytes = data.get(offset..end)?;
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+/// Handlers for plugin messages, by channel name (`namespace:path`).
+///
+/// Login handlers answer `LoginPluginRequest` packets (used by Velocity and BungeeCord forwarding).
+/// A request with no handler is answered as not understood, like the vanilla client does.
+/// Play handlers receive the custom payloads sent during the game.
+pub struct PluginChannels {
+    /// Sent on `minecraft:brand` once the bot has joined the game
+    pub brand: String,
+    login_handlers: BTreeMap<String, PluginMessageHandler>,
+    play_handlers: BTreeMap<String, PluginMessageHandler>,
+}
+
+impl PluginChannels {
+    pub fn new(brand: String) -> PluginChannels {
+        let mut plugin_channels = PluginChannels {
+            brand,
+            login_handlers: BTreeMap::new(),
+            play_handlers: BTreeMap::new(),
+        };
+        plugin_channels.on_play_message("minecraft:brand", |data| {
+            match decode_plugin_string(data) {
+                Some(brand) => info!("Server brand is {}", brand),
+                None => warn!("Server sent an invalid brand"),
+            }
+            None
+        });
+        plugin_channels
+    }
+
+    pub fn on_login_request(&mut self, channel: &str, handler: impl Fn(&[u8]) -> Option<Vec<u8>> + Send + Sync + 'static) {
+        self.login_handlers.insert(channel.to_string(), Box::new(handler));
+    }
+
+    pub fn on_play_message(&mut self, channel: &str, handler: impl Fn(&[u8]) -> Option<Vec<u8>> + Send + Sync + 'static) {
+        self.play_handlers.insert(channel.to_string(), Box::new(handler));
+    }
+
+    pub(crate) fn handle_login_request(&self, channel: &str, data: &[u8]) -> Option<Vec<u8>> {
+        match self.login_handlers.get(channel) {
+            Some(handler) => handler(data),
+            None => {
+                debug!("Ignoring login plugin request on {}", channel);
+                None
+            }
+        }
+    }
+
+    pub(crate) fn handle_play_message(&self, channel: &str, data: &[u8]) -> Option<Vec<u8>> {
+        match self.play_handlers.get(channel) {
+            Some(handler) => handler(data),
+            None => {
+                trace!("Ignoring plugin message on {} ({} bytes)", channel, data.len());
+                None
+            }
+        }
+    }
+
+    pub fn brand_payload(&self) -> Vec<u8> {
+        encode_plugin_string(&self.brand)
+    }
+}
+
+impl Default for PluginChannels {
+    fn default() -> Self {
+        PluginChannels::new("minecraft-bot".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string_codec() {
+        assert_eq!(encode_plugin_string("vanilla"), b"\x07vanilla");
+        assert_eq!(encode_plugin_string(""), [0]);
+        let long = "a".repeat(300);
+        let encoded = encode_plugin_string(&long);
+        assert_eq!(&encoded[..2], [0xAC, 0x02]);
+        assert_eq!(decode_plugin_string(&encoded), Some(long));
+        assert_eq!(decode_plugin_string(&encode_plugin_string("Paper")).as_deref(), Some("Paper"));
+    }
+
+    #[test]
+    fn test_invalid_strings() {
+        // Truncated data
+        assert_eq!(decode_plugin_string(b"\x07vanil"), None);
+        assert_eq!(decode_plugin_string(&[]), None);
+        // Negative length
+        assert_eq!(decode_plugin_string(&[0xFF, 0xFF, 0xFF, 0xFF, 0x0F, b'a']), None);
+        // Invalid UTF-8
+        assert_eq!(decode_plugin_string(&[2, 0xC3, 0x28]), None);
+    }
+
+    #[test]
+    fn test_handlers() {
+        let mut plugin_channels = PluginChannels::new("test".to_string());
+        plugin_channels.on_login_request("velocity:player_info", |data| Some(data.iter().rev().copied().collect()));
+        assert_eq!(plugin_channels.handle_login_request("velocity:player_info", &[1, 2, 3]), Some(vec![3, 2, 1]));
+        assert_eq!(plugin_channels.handle_login_request("unknown:channel", &[1, 2, 3]), None);
+        assert_eq!(plugin_channels.handle_play_message("minecraft:brand", &encode_plugin_string("Paper")), None);
+        assert_eq!(plugin_channels.brand_payload(), encode_plugin_string("test"));
+    }
+}
@@ -0,0 +1,311 @@
+use std::{
+    io::{Read, Write},
+    net::{IpAddr, TcpStream},
+};
+
+/// A proxy the connection to the server is tunneled through.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Proxy {
+    Socks5 {
+        addr: String,
+        port: u16,
+        /// Username and password
+        credentials: Option<(String, String)>,
+    },
+    HttpConnect {
+        addr: String,
+        port: u16,
+    },
+}
+
+impl Proxy {
+    /// Opens a tunnel to `addr:port` through this proxy.
+    /// The returned stream is ready for the Minecraft handshake.
+    pub fn open_tunnel(&self, addr: &str, port: u16) -> Result<TcpStream, String> {
+        match self {
+            Proxy::Socks5 {
+                addr: proxy_addr,
+                port: proxy_port,
+                credentials,
+            } => {
+                let mut stream = TcpStream::connect((proxy_addr.as_str(), *proxy_port)).map_err(|e| format!("Failed to connect to proxy: {}", e))?;
+                socks5_handshake(&mut stream, addr, port, credentials.as_ref()).map_err(|e| format!("SOCKS5 proxy error: {}", e))?;
+                Ok(stream)
+            }
+            Proxy::HttpConnect {
+                addr: proxy_addr,
+                port: proxy_port,
+            } => {
+                let mut stream = TcpStream::connect((proxy_addr.as_str(), *proxy_port)).map_err(|e| format!("Failed to connect to proxy: {}", e))?;
+                http_connect_handshake(&mut stream, addr, port).map_err(|e| format!("HTTP proxy error: {}", e))?;
+                Ok(stream)
+            }
+        }
+    }
+}
+
+/// Connects to `addr:port` directly or through a proxy.
+pub fn open_stream(addr: &str, port: u16, proxy: Option<&Proxy>) -> Result<TcpStream, String> {
+    match proxy {
+        Some(proxy) => proxy.open_tunnel(addr, port),
+        None => TcpStream::connect((addr, port)).map_err(|e| format!("Failed to connect: {}", e)),
+    }
+}
+
+/// Formats `addr:port`, with brackets around IPv6 addresses
+pub fn format_authority(addr: &str, port: u16) -> String {
+    match addr.parse::<IpAddr>() {
+        Ok(IpAddr::V6(ip)) => format!("[{}]:{}", ip, port),
+        _ => format!("{}:{}", addr, port),
+    }
+}
+
+fn io_error(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, message)
+}
+
+fn socks5_handshake(stream: &mut TcpStream, addr: &str, port: u16, credentials: Option<&(String, String)>) -> std::io::Result<()> {
+    // Greeting: version 5 with the supported authentication methods
+    match credentials {
+        Some(_) => stream.write_all(&[5, 2, 0x00, 0x02])?,
+        None => stream.write_all(&[5, 1, 0x00])?,
+    }
+    let mut response = [0; 2];
+    stream.read_exact(&mut response)?;
+    if response[0] != 5 {
+        return Err(io_error(format!("Unexpected SOCKS version {}", response[0])));
+    }
+    match (response[1], credentials) {
+        (0x00, _) => (),
+        (0x02, Some((username, password))) => {
+            if username.len() > 255 || password.len() > 255 {
+                return Err(io_error("Credentials are too long".to_string()));
+            }
+            let mut request = vec![1, username.len() as u8];
+            request.extend_from_slice(username.as_bytes());
+            request.push(password.len() as u8);
+            request.extend_from_slice(password.as_bytes());
+            stream.write_all(&request)?;
+
+            let mut response = [0; 2];
+            stream.read_exact(&mut response)?;
+            if response[1] != 0 {
+                return Err(io_error("Authentication refused".to_string()));
+            }
+        }
+        (method, _) => return Err(io_error(format!("No acceptable authentication method (server chose {})", method))),
+    }
+
+    // Connect command. Domain names are resolved by the proxy.
+    let mut request = vec![5, 1, 0];
+    match addr.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            if addr.len() > 255 {
+                return Err(io_error("Server address is too long".to_string()));
+            }
+            request.push(3);
+            request.push(addr.len() as u8);
+            request.extend_from_slice(addr.as_bytes());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request)?;
+
+    let mut response = [0; 4];
+    stream.read_exact(&mut response)?;
+    if response[1] != 0 {
+        return Err(io_error(format!("Connection refused by proxy (reply {})", response[1])));
+    }
+
+    // Skip the bound address
+    let address_len = match response[3] {
+        1 => 4,
+        3 => {
+            let mut len = [0; 1];
+            stream.read_exact(&mut len)?;
+            len[0] as usize
+        }
+        4 => 16,
+        address_type => return Err(io_error(format!("Unknown address type {}", address_type))),
+    };
+    let mut bound_address = vec![0; address_len + 2];
+    stream.read_exact(&mut bound_address)?;
+
+    Ok(())
+}
+
+fn http_connect_handshake(stream: &mut TcpStream, addr: &str, port: u16) -> std::io::Result<()> {
+    let authority = format_authority(addr, port);
+    write!(stream, "CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n\r\n", authority)?;
+
+    // Read the response headers byte by byte to avoid consuming data from the tunnel
+    let mut response = Vec::new();
+    let mut byte = [0; 1];
+    while !response.ends_with(b"\r\n\r\n") {
+        stream.read_exact(&mut byte)?;
+        response.push(byte[0]);
+        if response.len() > 8192 {
+            return Err(io_error("Response headers are too long".to_string()));
+        }
+    }
+
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some("200") => Ok(()),
+        _ => Err(io_error(format!("Proxy refused to connect: {}", status_line))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::TcpListener, thread::JoinHandle};
+
+    /// Runs a fake proxy accepting one connection.
+    /// `handshake` checks what the client sent and answers, then the proxy echoes what the client writes in the tunnel.
+    fn proxy_stand_in<T: Send + 'static>(handshake: impl FnOnce(&mut TcpStream) -> T + Send + 'static) -> (u16, JoinHandle<T>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let result = handshake(&mut stream);
+            let mut buf = [0; 4];
+            if stream.read_exact(&mut buf).is_ok() {
+                stream.write_all(&buf).unwrap();
+            }
+            result
+        });
+        (port, handle)
+    }
+
+    fn read_bytes(stream: &mut TcpStream, len: usize) -> Vec<u8> {
+        let mut buf = vec![0; len];
+        stream.read_exact(&mut buf).unwrap();
+        buf
+    }
+
+    fn assert_tunnel_works(mut stream: TcpStream) {
+        stream.write_all(b"ping").unwrap();
+        assert_eq!(read_bytes(&mut stream, 4), b"ping");
+    }
+
+    #[test]
+    fn test_socks5_domain() {
+        let (port, proxy) = proxy_stand_in(|stream| {
+            assert_eq!(read_bytes(stream, 3), [5, 1, 0]);
+            stream.write_all(&[5, 0]).unwrap();
+            let request = read_bytes(stream, 5 + 11 + 2);
+            stream.write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0x63, 0xdd]).unwrap();
+            request
+        });
+        let proxy_config = Proxy::Socks5 {
+            addr: "127.0.0.1".to_string(),
+            port,
+            credentials: None,
+        };
+        assert_tunnel_works(proxy_config.open_tunnel("example.com", 25565).unwrap());
+
+        let mut expected = vec![5, 1, 0, 3, 11];
+        expected.extend_from_slice(b"example.com");
+        expected.extend_from_slice(&25565u16.to_be_bytes());
+        assert_eq!(proxy.join().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_socks5_credentials_and_ipv6() {
+        let (port, proxy) = proxy_stand_in(|stream| {
+            assert_eq!(read_bytes(stream, 4), [5, 2, 0, 2]);
+            stream.write_all(&[5, 2]).unwrap();
+            assert_eq!(read_bytes(stream, 2 + 4 + 1 + 6), b"\x01\x04user\x06secret");
+            stream.write_all(&[1, 0]).unwrap();
+            let request = read_bytes(stream, 4 + 16 + 2);
+            stream.write_all(&[5, 0, 0, 3, 4, b'h', b'o', b's', b't', 0x63, 0xdd]).unwrap();
+            request
+        });
+        let proxy_config = Proxy::Socks5 {
+            addr: "127.0.0.1".to_string(),
+            port,
+            credentials: Some(("user".to_string(), "secret".to_string())),
+        };
+        assert_tunnel_works(proxy_config.open_tunnel("::1", 25565).unwrap());
+
+        let request = proxy.join().unwrap();
+        assert_eq!(request[..4], [5, 1, 0, 4]);
+        assert_eq!(request[4..20], "::1".parse::<std::net::Ipv6Addr>().unwrap().octets());
+        assert_eq!(request[20..], 25565u16.to_be_bytes());
+    }
+
+    #[test]
+    fn test_socks5_refused() {
+        let (port, proxy) = proxy_stand_in(|stream| {
+            read_bytes(stream, 3);
+            stream.write_all(&[5, 0xff]).unwrap();
+        });
+        let proxy_config = Proxy::Socks5 {
+            addr: "127.0.0.1".to_string(),
+            port,
+            credentials: None,
+        };
+        assert!(proxy_config.open_tunnel("example.com", 25565).is_err());
+        proxy.join().unwrap();
+    }
+
+    /// Reads the request headers of a HTTP CONNECT and answers with a status line
+    fn http_handshake(stream: &mut TcpStream, status: &str) -> String {
+        let mut request = Vec::new();
+        while !request.ends_with(b"\r\n\r\n") {
+            request.extend(read_bytes(stream, 1));
+        }
+        write!(stream, "HTTP/1.1 {}\r\nProxy-Agent: test\r\n\r\n", status).unwrap();
+        String::from_utf8(request).unwrap()
+    }
+
+    #[test]
+    fn test_http_connect() {
+        let (port, proxy) = proxy_stand_in(|stream| http_handshake(stream, "200 Connection established"));
+        let proxy_config = Proxy::HttpConnect {
+            addr: "127.0.0.1".to_string(),
+            port,
+        };
+        assert_tunnel_works(proxy_config.open_tunnel("example.com", 25565).unwrap());
+        assert_eq!(proxy.join().unwrap(), "CONNECT example.com:25565 HTTP/1.1\r\nHost: example.com:25565\r\n\r\n");
+    }
+
+    #[test]
+    fn test_http_connect_ipv6() {
+        let (port, proxy) = proxy_stand_in(|stream| http_handshake(stream, "200 OK"));
+        let proxy_config = Proxy::HttpConnect {
+            addr: "127.0.0.1".to_string(),
+            port,
+        };
+        assert_tunnel_works(proxy_config.open_tunnel("::1", 25565).unwrap());
+        assert!(proxy.join().unwrap().starts_with("CONNECT [::1]:25565 HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn test_http_connect_refused() {
+        let (port, proxy) = proxy_stand_in(|stream| http_handshake(stream, "403 Forbidden"));
+        let proxy_config = Proxy::HttpConnect {
+            addr: "127.0.0.1".to_string(),
+            port,
+        };
+        assert!(proxy_config.open_tunnel("example.com", 25565).is_err());
+        proxy.join().unwrap();
+    }
+
+    #[test]
+    fn test_format_authority() {
+        assert_eq!(format_authority("example.com", 25565), "example.com:25565");
+        assert_eq!(format_authority("127.0.0.1", 1), "127.0.0.1:1");
+        assert_eq!(format_authority("::1", 25565), "[::1]:25565");
+    }
+}
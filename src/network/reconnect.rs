@@ -0,0 +1,129 @@
+use std::time::Duration;
+
+/// How the bot tries to reconnect after losing its connection.
+/// The delay between two attempts grows exponentially, from `initial_delay` up to `max_delay`.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    /// Maximum number of consecutive failed attempts. `None` means the bot never gives up.
+    pub max_retries: Option<u32>,
+}
+
+impl ReconnectPolicy {
+    /// Creates a policy, checking that the delays can be computed
+    pub fn new(initial_delay: Duration, max_delay: Duration, multiplier: f64, max_retries: Option<u32>) -> Result<ReconnectPolicy, String> {
+        let policy = ReconnectPolicy {
+            initial_delay,
+            max_delay,
+            multiplier,
+            max_retries,
+        };
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.multiplier.is_finite() || self.multiplier < 1.0 {
+            return Err(format!("Invalid reconnection delay multiplier {}, it must be finite and at least 1", self.multiplier));
+        }
+        if self.initial_delay > self.max_delay {
+            return Err(format!("Initial reconnection delay {:?} is longer than the maximum {:?}", self.initial_delay, self.max_delay));
+        }
+        Ok(())
+    }
+
+    /// Never reconnect.
+    pub fn disabled() -> ReconnectPolicy {
+        ReconnectPolicy {
+            max_retries: Some(0),
+            ..ReconnectPolicy::default()
+        }
+    }
+
+    pub fn should_retry(&self, attempt: u32) -> bool {
+        match self.max_retries {
+            Some(max_retries) => attempt < max_retries,
+            None => true,
+        }
+    }
+
+    /// The delay to wait before the attempt number `attempt` (starting at 0).
+    /// Invalid policies (see [ReconnectPolicy::validate]) never make it panic.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = self.initial_delay.as_secs_f64() * self.multiplier.powi(attempt.min(i32::MAX as u32) as i32);
+        if delay.is_nan() || delay <= 0.0 {
+            return Duration::from_secs(0);
+        }
+        if delay > self.max_delay.as_secs_f64() {
+            return self.max_delay;
+        }
+        Duration::from_secs_f64(delay)
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            multiplier: 2.0,
+            max_retries: Some(10),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exponential_backoff() {
+        let policy = ReconnectPolicy::new(Duration::from_secs(1), Duration::from_secs(10), 2.0, Some(5)).unwrap();
+        assert_eq!(policy.delay(0), Duration::from_secs(1));
+        assert_eq!(policy.delay(1), Duration::from_secs(2));
+        assert_eq!(policy.delay(3), Duration::from_secs(8));
+        assert_eq!(policy.delay(4), Duration::from_secs(10));
+        assert_eq!(policy.delay(u32::MAX), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_retries() {
+        let policy = ReconnectPolicy::default();
+        assert!(policy.should_retry(0));
+        assert!(policy.should_retry(9));
+        assert!(!policy.should_retry(10));
+        assert!(!ReconnectPolicy::disabled().should_retry(0));
+        let forever = ReconnectPolicy {
+            max_retries: None,
+            ..ReconnectPolicy::default()
+        };
+        assert!(forever.should_retry(u32::MAX));
+    }
+
+    #[test]
+    fn test_invalid_policies() {
+        assert!(ReconnectPolicy::new(Duration::from_secs(1), Duration::from_secs(10), f64::INFINITY, None).is_err());
+        assert!(ReconnectPolicy::new(Duration::from_secs(1), Duration::from_secs(10), f64::NAN, None).is_err());
+        assert!(ReconnectPolicy::new(Duration::from_secs(1), Duration::from_secs(10), -2.0, None).is_err());
+        assert!(ReconnectPolicy::new(Duration::from_secs(20), Duration::from_secs(10), 2.0, None).is_err());
+
+        // Invalid policies built by hand don't panic
+        for multiplier in [-2.0, f64::NAN, f64::INFINITY, 0.0] {
+            let policy = ReconnectPolicy {
+                initial_delay: Duration::from_secs(0),
+                multiplier,
+                ..ReconnectPolicy::default()
+            };
+            for attempt in 0..4 {
+                assert!(policy.delay(attempt) <= policy.max_delay);
+            }
+        }
+        let negative = ReconnectPolicy {
+            multiplier: -2.0,
+            ..ReconnectPolicy::default()
+        };
+        assert_eq!(negative.delay(1), Duration::from_secs(0));
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+const MAGIC: &[u8; 4] = b"MCBR";
+const FORMAT_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketDirection {
+    Clientbound,
+    Serverbound,
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordedPacket {
+    /// Time elapsed since the beginning of the recording
+    pub time: Duration,
+    pub direction: PacketDirection,
+    /// The raw packet, uncompressed and decrypted
+    pub data: Vec<u8>,
+}
+
+/// Writes every packet going through a connection to a file.
+///
+/// The file starts with a header (`MCBR` and a format version byte), followed by records made of
+/// the timestamp in milliseconds (u32), the direction (u8), the length (u32) and the packet data.
+/// Integers are little endian.
+pub struct PacketRecorder {
+    writer: BufWriter<File>,
+    start: Instant,
+}
+
+impl PacketRecorder {
+    pub fn create(path: impl AsRef<Path>) -> std::io::Result<PacketRecorder> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[FORMAT_VERSION])?;
+        Ok(PacketRecorder { writer, start: Instant::now() })
+    }
+
+    pub fn record(&mut self, direction: PacketDirection, data: &[u8]) -> std::io::Result<()> {
+        let time = self.start.elapsed().as_millis().min(u32::MAX as u128) as u32;
+        self.writer.write_all(&time.to_le_bytes())?;
+        self.writer.write_all(&[match direction {
+            PacketDirection::Clientbound => 0,
+            PacketDirection::Serverbound => 1,
+        }])?;
+        self.writer.write_all(&(data.len() as u32).to_le_bytes())?;
+        self.writer.write_all(data)?;
+        // Flushing every packet makes sure the recording is usable even if the bot crashes
+        self.writer.flush()
+    }
+}
+
+/// Reads the packets of a file written by a [PacketRecorder].
+pub struct PacketRecording {
+    reader: BufReader<File>,
+}
+
+impl PacketRecording {
+    pub fn open(path: impl AsRef<Path>) -> Result<PacketRecording, String> {
+        let mut reader = BufReader::new(File::open(path).map_err(|e| format!("Failed to open recording: {}", e))?);
+        let mut header = [0; 5];
+        reader.read_exact(&mut header).map_err(|e| format!("Failed to read recording header: {}", e))?;
+        if &header[..4] != MAGIC {
+            return Err("Not a packet recording".to_string());
+        }
+        if header[4] != FORMAT_VERSION {
+            return Err(format!("Unsupported recording format version {}", header[4]));
+        }
+        Ok(PacketRecording { reader })
+    }
+
+    fn read_packet(&mut self) -> std::io::Result<RecordedPacket> {
+        let mut buffer = [0; 4];
+        self.reader.read_exact(&mut buffer)?;
+        let time = Duration::from_millis(u32::from_le_bytes(buffer) as u64);
+
+        let mut direction = [0; 1];
+        self.reader.read_exact(&mut direction)?;
+        let direction = match direction[0] {
+            0 => PacketDirection::Clientbound,
+            1 => PacketDirection::Serverbound,
+            _ => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid packet direction")),
+        };
+
+        self.reader.read_exact(&mut buffer)?;
+        let mut data = vec![0; u32::from_le_bytes(buffer) as usize];
+        self.reader.read_exact(&mut data)?;
+
+        Ok(RecordedPacket { time, direction, data })
+    }
+}
+
+impl Iterator for PacketRecording {
+    type Item = RecordedPacket;
+
+    fn next(&mut self) -> Option<RecordedPacket> {
+        match self.read_packet() {
+            Ok(packet) => Some(packet),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => None,
+            Err(e) => {
+                log::error!("Failed to read recorded packet: {}", e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("minecraft-bot-{}-{}.mcbr", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let path = temp_path("round-trip");
+        let mut recorder = PacketRecorder::create(&path).unwrap();
+        recorder.record(PacketDirection::Clientbound, &[0x21, 1, 2, 3]).unwrap();
+        recorder.record(PacketDirection::Serverbound, &[0x0f]).unwrap();
+        recorder.record(PacketDirection::Clientbound, &[]).unwrap();
+        drop(recorder);
+
+        let packets: Vec<RecordedPacket> = PacketRecording::open(&path).unwrap().collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(packets.len(), 3);
+        assert_eq!(packets[0].direction, PacketDirection::Clientbound);
+        assert_eq!(packets[0].data, vec![0x21, 1, 2, 3]);
+        assert_eq!(packets[1].direction, PacketDirection::Serverbound);
+        assert_eq!(packets[1].data, vec![0x0f]);
+        assert!(packets[2].data.is_empty());
+        assert!(packets[0].time <= packets[1].time && packets[1].time <= packets[2].time);
+    }
+
+    #[test]
+    fn test_replay() {
+        use crate::{ClientboundPacket, ServerboundPacket};
+        use minecraft_protocol::MinecraftPacketPart;
+
+        // A session where the bot answers two keep alives
+        let path = temp_path("replay");
+        let mut recorder = PacketRecorder::create(&path).unwrap();
+        let mut answers = Vec::new();
+        for keep_alive_id in [7, 8] {
+            let request = ClientboundPacket::KeepAlive { keep_alive_id }.serialize_minecraft_packet().unwrap();
+            let answer = ServerboundPacket::KeepAlive { keep_alive_id }.serialize_minecraft_packet().unwrap();
+            recorder.record(PacketDirection::Clientbound, &request).unwrap();
+            recorder.record(PacketDirection::Serverbound, &answer).unwrap();
+            answers.push(answer);
+        }
+        drop(recorder);
+
+        let (_, report) = crate::replay::replay(&path, "bot".to_string()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(report.clientbound_packets, 2);
+        assert_eq!(report.recorded_serverbound_packets, 2);
+        assert_eq!(report.parse_failures, 0);
+        assert_eq!(report.sent_packets, answers);
+    }
+
+    #[test]
+    fn test_invalid_files() {
+        let path = temp_path("invalid");
+        std::fs::write(&path, b"NOPE\x01").unwrap();
+        assert!(PacketRecording::open(&path).is_err());
+        std::fs::write(&path, b"MCBR\x02").unwrap();
+        assert!(PacketRecording::open(&path).is_err());
+
+        // A truncated record ends the recording
+        std::fs::write(&path, b"MCBR\x01\x00\x00\x00\x00\x00\x05\x00\x00\x00ab").unwrap();
+        assert_eq!(PacketRecording::open(&path).unwrap().count(), 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,402 @@
+use log::*;
+use minecraft_protocol::{packets::play_serverbound::ServerboundPacket, MinecraftPacketPart};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Classes of serverbound packets, from the most to the least urgent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PacketPriority {
+    /// Keep alives and teleport confirmations. Delaying them gets the bot kicked or teleported back.
+    Urgent,
+    /// Moves
+    Movement,
+    /// Digging, placing blocks, clicking in windows...
+    Action,
+    Chat,
+}
+
+impl PacketPriority {
+    const ALL: [PacketPriority; 4] = [PacketPriority::Urgent, PacketPriority::Movement, PacketPriority::Action, PacketPriority::Chat];
+
+    pub fn of(packet: &ServerboundPacket) -> PacketPriority {
+        match packet {
+            ServerboundPacket::KeepAlive { .. } | ServerboundPacket::TeleportConfirm { .. } => PacketPriority::Urgent,
+            ServerboundPacket::PlayerPosition { .. }
+            | ServerboundPacket::PlayerPositionAndRotation { .. }
+            | ServerboundPacket::PlayerRotation { .. }
+            | ServerboundPacket::PlayerMovement { .. } => PacketPriority::Movement,
+            ServerboundPacket::ChatMessage { .. } => PacketPriority::Chat,
+            _ => PacketPriority::Action,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// A token bucket: `burst` packets can be sent at once, then `packets_per_second`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub packets_per_second: f64,
+    pub burst: u32,
+}
+
+/// Rate limits of each packet class. `None` means unlimited.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimits {
+    pub urgent: Option<RateLimit>,
+    pub movement: Option<RateLimit>,
+    pub action: Option<RateLimit>,
+    pub chat: Option<RateLimit>,
+}
+
+impl RateLimits {
+    pub fn unlimited() -> RateLimits {
+        RateLimits {
+            urgent: None,
+            movement: None,
+            action: None,
+            chat: None,
+        }
+    }
+
+    fn get(&self, priority: PacketPriority) -> Option<RateLimit> {
+        match priority {
+            PacketPriority::Urgent => self.urgent,
+            PacketPriority::Movement => self.movement,
+            PacketPriority::Action => self.action,
+            PacketPriority::Chat => self.chat,
+        }
+    }
+}
+
+impl Default for RateLimits {
+    /// Limits that stay below the vanilla spam protections.
+    /// Chat is not limited here since [Bot::say](crate::Bot::say) already paces the messages, see [ChatQueue](crate::ChatQueue).
+    fn default() -> Self {
+        RateLimits {
+            urgent: None,
+            movement: None,
+            action: Some(RateLimit {
+                packets_per_second: 20.0,
+                burst: 40,
+            }),
+            chat: None,
+        }
+    }
+}
+
+struct TokenBucket {
+    limit: Option<RateLimit>,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: Option<RateLimit>) -> TokenBucket {
+        TokenBucket {
+            limit,
+            tokens: limit.map(|l| l.burst as f64).unwrap_or_default(),
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if let Some(limit) = self.limit {
+            let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * limit.packets_per_second).min(limit.burst.max(1) as f64);
+        }
+        self.last_refill = now;
+    }
+
+    fn try_take(&mut self) -> bool {
+        match self.limit {
+            None => true,
+            Some(_) if self.tokens >= 1.0 => {
+                self.tokens -= 1.0;
+                true
+            }
+            Some(_) => false,
+        }
+    }
+
+    /// Time until a token is available
+    fn wait_time(&self) -> Duration {
+        match self.limit {
+            Some(limit) if self.tokens < 1.0 && limit.packets_per_second > 0.0 => Duration::from_secs_f64((1.0 - self.tokens) / limit.packets_per_second),
+            Some(limit) if self.tokens < 1.0 => {
+                warn!("Rate limit {:?} never allows sending packets", limit);
+                Duration::from_secs(1)
+            }
+            _ => Duration::from_secs(0),
+        }
+    }
+}
+
+/// Orders the outgoing packets by priority and applies the rate limits.
+///
+/// Packets of a class are sent in order.
+/// Actions also wait for the moves queued before them since blocks are dug and placed from where the bot stands.
+/// Moves never wait for actions, so a rate limited action cannot delay them.
+pub struct PacketScheduler {
+    /// Packets with the order in which they were pushed
+    queues: [VecDeque<(u64, Vec<u8>)>; 4],
+    buckets: [TokenBucket; 4],
+    next_sequence: u64,
+}
+
+impl PacketScheduler {
+    pub fn new(rate_limits: &RateLimits) -> PacketScheduler {
+        PacketScheduler {
+            queues: [VecDeque::new(), VecDeque::new(), VecDeque::new(), VecDeque::new()],
+            buckets: [
+                TokenBucket::new(rate_limits.get(PacketPriority::Urgent)),
+                TokenBucket::new(rate_limits.get(PacketPriority::Movement)),
+                TokenBucket::new(rate_limits.get(PacketPriority::Action)),
+                TokenBucket::new(rate_limits.get(PacketPriority::Chat)),
+            ],
+            next_sequence: 0,
+        }
+    }
+
+    pub fn push(&mut self, priority: PacketPriority, packet: Vec<u8>) {
+        self.queues[priority.index()].push_back((self.next_sequence, packet));
+        self.next_sequence += 1;
+    }
+
+    /// Whether the first packet of a class has to wait for an older move
+    fn is_blocked(&self, priority: PacketPriority) -> bool {
+        if priority != PacketPriority::Action {
+            return false;
+        }
+        match (self.queues[PacketPriority::Action.index()].front(), self.queues[PacketPriority::Movement.index()].front()) {
+            (Some((action, _)), Some((movement, _))) => movement < action,
+            _ => false,
+        }
+    }
+
+    /// Returns the next packet that can be sent now, if any.
+    pub fn pop_ready(&mut self, now: Instant) -> Option<Vec<u8>> {
+        for priority in PacketPriority::ALL.iter() {
+            let idx = priority.index();
+            if self.queues[idx].is_empty() || self.is_blocked(*priority) {
+                continue;
+            }
+            self.buckets[idx].refill(now);
+            if self.buckets[idx].try_take() {
+                return self.queues[idx].pop_front().map(|(_, packet)| packet);
+            }
+        }
+        None
+    }
+
+    /// How long to wait before a queued packet can be sent. `None` if nothing is queued.
+    pub fn next_ready_in(&self) -> Option<Duration> {
+        PacketPriority::ALL
+            .iter()
+            .filter(|priority| !self.queues[priority.index()].is_empty() && !self.is_blocked(**priority))
+            .map(|priority| self.buckets[priority.index()].wait_time())
+            .min()
+    }
+}
+
+/// The channel under a [PacketSender], depending on what drives the connection.
+#[derive(Clone)]
+enum ChannelSender {
+    /// Read by the sending thread of [connect](super::connect)
+    Std(mpsc::Sender<(PacketPriority, Vec<u8>)>),
+    /// Awaited by the sending task of [connect_async](super::connect_async)
+    #[cfg(feature = "async")]
+    Tokio {
+        sender: tokio::sync::mpsc::UnboundedSender<(PacketPriority, Vec<u8>)>,
+        /// Wakes the sending task up when the connection is closed while other senders are still alive
+        closed: Arc<tokio::sync::Notify>,
+    },
+}
+
+/// The sending end of the outgoing packet queue of a connection.
+#[derive(Clone)]
+pub struct PacketSender {
+    sender: ChannelSender,
+    running: Arc<AtomicBool>,
+}
+
+impl PacketSender {
+    pub fn channel() -> (PacketSender, mpsc::Receiver<(PacketPriority, Vec<u8>)>) {
+        let (sender, receiver) = mpsc::channel();
+        let running = Arc::new(AtomicBool::new(true));
+        (
+            PacketSender {
+                sender: ChannelSender::Std(sender),
+                running,
+            },
+            receiver,
+        )
+    }
+
+    /// Same as [PacketSender::channel] but the receiver can be awaited.
+    /// The returned [Notify](tokio::sync::Notify) is notified when the connection is closed.
+    #[cfg(feature = "async")]
+    pub(crate) fn async_channel() -> (
+        PacketSender,
+        tokio::sync::mpsc::UnboundedReceiver<(PacketPriority, Vec<u8>)>,
+        Arc<tokio::sync::Notify>,
+    ) {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let closed = Arc::new(tokio::sync::Notify::new());
+        let running = Arc::new(AtomicBool::new(true));
+        (
+            PacketSender {
+                sender: ChannelSender::Tokio {
+                    sender,
+                    closed: Arc::clone(&closed),
+                },
+                running,
+            },
+            receiver,
+            closed,
+        )
+    }
+
+    /// The flag shared with the network threads of the connection
+    pub(crate) fn running(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.running)
+    }
+
+    /// Closes the connection, even if other senders are still alive.
+    /// The packets that are still queued are dropped.
+    pub fn close(&self) {
+        self.running.store(false, Ordering::SeqCst);
+        #[cfg(feature = "async")]
+        if let ChannelSender::Tokio { closed, .. } = &self.sender {
+            closed.notify_one();
+        }
+    }
+
+    /// Queues a packet. Nothing happens if the connection is already closed.
+    pub fn send(&self, packet: ServerboundPacket) {
+        let priority = PacketPriority::of(&packet);
+        match packet.serialize_minecraft_packet() {
+            Ok(packet) => self.send_raw(priority, packet),
+            Err(e) => error!("Failed to serialize packet from client {}", e),
+        }
+    }
+
+    pub fn send_raw(&self, priority: PacketPriority, packet: Vec<u8>) {
+        let sent = match &self.sender {
+            ChannelSender::Std(sender) => sender.send((priority, packet)).is_ok(),
+            #[cfg(feature = "async")]
+            ChannelSender::Tokio { sender, .. } => sender.send((priority, packet)).is_ok(),
+        };
+        if !sent {
+            trace!("Dropped a packet since the connection is closed");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limited(packets_per_second: f64, burst: u32) -> Option<RateLimit> {
+        Some(RateLimit { packets_per_second, burst })
+    }
+
+    #[test]
+    fn test_priorities() {
+        let mut scheduler = PacketScheduler::new(&RateLimits::unlimited());
+        let now = Instant::now();
+        scheduler.push(PacketPriority::Chat, vec![3]);
+        scheduler.push(PacketPriority::Movement, vec![1]);
+        scheduler.push(PacketPriority::Urgent, vec![0]);
+        scheduler.push(PacketPriority::Action, vec![2]);
+        assert_eq!(scheduler.pop_ready(now), Some(vec![0]));
+        assert_eq!(scheduler.pop_ready(now), Some(vec![1]));
+        assert_eq!(scheduler.pop_ready(now), Some(vec![2]));
+        assert_eq!(scheduler.pop_ready(now), Some(vec![3]));
+        assert_eq!(scheduler.pop_ready(now), None);
+        assert_eq!(scheduler.next_ready_in(), None);
+    }
+
+    #[test]
+    fn test_actions_do_not_delay_moves() {
+        let mut scheduler = PacketScheduler::new(&RateLimits {
+            action: limited(1.0, 1),
+            ..RateLimits::unlimited()
+        });
+        let now = Instant::now();
+        scheduler.push(PacketPriority::Action, vec![0]);
+        scheduler.push(PacketPriority::Action, vec![1]);
+        scheduler.push(PacketPriority::Movement, vec![2]);
+        scheduler.push(PacketPriority::Urgent, vec![3]);
+        assert_eq!(scheduler.pop_ready(now), Some(vec![3]));
+        // The move overtakes the rate limited actions queued before it
+        assert_eq!(scheduler.pop_ready(now), Some(vec![2]));
+        assert_eq!(scheduler.pop_ready(now), Some(vec![0]));
+        assert_eq!(scheduler.pop_ready(now), None);
+        assert!(scheduler.next_ready_in().unwrap() > Duration::from_millis(900));
+        assert_eq!(scheduler.pop_ready(now + Duration::from_secs(1)), Some(vec![1]));
+    }
+
+    #[test]
+    fn test_teleport_confirm_is_urgent() {
+        let confirm = ServerboundPacket::TeleportConfirm {
+            teleport_id: minecraft_protocol::packets::VarInt(0),
+        };
+        assert_eq!(PacketPriority::of(&confirm), PacketPriority::Urgent);
+
+        let mut scheduler = PacketScheduler::new(&RateLimits {
+            movement: limited(1.0, 1),
+            action: limited(1.0, 1),
+            ..RateLimits::unlimited()
+        });
+        let now = Instant::now();
+        scheduler.push(PacketPriority::Movement, vec![0]);
+        scheduler.push(PacketPriority::Movement, vec![1]);
+        scheduler.push(PacketPriority::of(&confirm), vec![2]);
+        assert_eq!(scheduler.pop_ready(now), Some(vec![2]));
+        assert_eq!(scheduler.pop_ready(now), Some(vec![0]));
+        assert_eq!(scheduler.pop_ready(now), None);
+    }
+
+    #[test]
+    fn test_actions_wait_for_older_moves() {
+        let mut scheduler = PacketScheduler::new(&RateLimits {
+            movement: limited(1.0, 1),
+            ..RateLimits::unlimited()
+        });
+        let now = Instant::now();
+        scheduler.push(PacketPriority::Movement, vec![0]);
+        scheduler.push(PacketPriority::Movement, vec![1]);
+        scheduler.push(PacketPriority::Action, vec![2]);
+        assert_eq!(scheduler.pop_ready(now), Some(vec![0]));
+        // The action is dug from the position sent by the rate limited move
+        assert_eq!(scheduler.pop_ready(now), None);
+        let later = now + Duration::from_secs(1);
+        assert_eq!(scheduler.pop_ready(later), Some(vec![1]));
+        assert_eq!(scheduler.pop_ready(later), Some(vec![2]));
+    }
+
+    #[test]
+    fn test_chat_does_not_block() {
+        let mut scheduler = PacketScheduler::new(&RateLimits {
+            chat: limited(1.0, 1),
+            ..RateLimits::unlimited()
+        });
+        let now = Instant::now();
+        scheduler.push(PacketPriority::Chat, vec![0]);
+        scheduler.push(PacketPriority::Chat, vec![1]);
+        scheduler.push(PacketPriority::Movement, vec![2]);
+        assert_eq!(scheduler.pop_ready(now), Some(vec![2]));
+        assert_eq!(scheduler.pop_ready(now), Some(vec![0]));
+        assert_eq!(scheduler.pop_ready(now), None);
+        assert_eq!(scheduler.pop_ready(now + Duration::from_secs(1)), Some(vec![1]));
+    }
+}
@@ -0,0 +1,195 @@
+use super::decode_varint;
+use crate::{ClientboundPacket, ServerboundPacket};
+use minecraft_protocol::MinecraftPacketPart;
+use std::{
+    collections::BTreeMap,
+    convert::TryInto,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// The ids of the clientbound and serverbound keep alive packets, as defined by the protocol crate
+fn keep_alive_ids() -> (Option<i32>, Option<i32>) {
+    let packet_id = |packet: Result<Vec<u8>, _>| packet.ok().and_then(|packet| decode_varint(&packet)).map(|(id, _)| id);
+    (
+        packet_id(ClientboundPacket::KeepAlive { keep_alive_id: 0 }.serialize_minecraft_packet()),
+        packet_id(ServerboundPacket::KeepAlive { keep_alive_id: 0 }.serialize_minecraft_packet()),
+    )
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PacketCounter {
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+impl PacketCounter {
+    fn add(&mut self, bytes: usize) {
+        self.packets += 1;
+        self.bytes += bytes as u64;
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HandlingTime {
+    pub count: u64,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+impl HandlingTime {
+    pub fn average(&self) -> Duration {
+        match self.count {
+            0 => Duration::from_secs(0),
+            count => self.total / count as u32,
+        }
+    }
+}
+
+/// Counters of a connection. Sizes are those of the uncompressed packets.
+/// Packets are identified by their id in the play state.
+#[derive(Debug, Default, Clone)]
+pub struct NetworkStats {
+    pub received: PacketCounter,
+    pub sent: PacketCounter,
+    pub received_by_id: BTreeMap<i32, PacketCounter>,
+    pub sent_by_id: BTreeMap<i32, PacketCounter>,
+    /// Time spent in [crate::Bot::update] for each clientbound packet id
+    pub update_time_by_id: BTreeMap<i32, HandlingTime>,
+    /// Delay between the moment the server sent a keep alive and the moment the bot received it, smoothed like vanilla does.
+    /// Servers that use their clock (in milliseconds) as keep alive id are supported, other ids are ignored.
+    pub latency: Option<Duration>,
+    /// Time the bot took to answer the last keep alive, from the socket to the socket.
+    pub keep_alive_turnaround: Option<Duration>,
+    pending_keep_alive: Option<(Vec<u8>, Instant)>,
+    keep_alive_ids: Option<(Option<i32>, Option<i32>)>,
+}
+
+impl NetworkStats {
+    pub(crate) fn record_received(&mut self, packet: &[u8]) {
+        self.received.add(packet.len());
+        let (keep_alive_id, _) = *self.keep_alive_ids.get_or_insert_with(keep_alive_ids);
+        if let Some((id, len)) = decode_varint(packet) {
+            self.received_by_id.entry(id).or_default().add(packet.len());
+            if Some(id) == keep_alive_id {
+                if let Ok(keep_alive_id) = packet[len..].try_into() {
+                    self.record_keep_alive(i64::from_be_bytes(keep_alive_id), SystemTime::now());
+                }
+                self.pending_keep_alive = Some((packet[len..].to_vec(), Instant::now()));
+            }
+        }
+    }
+
+    pub(crate) fn record_sent(&mut self, packet: &[u8]) {
+        self.sent.add(packet.len());
+        let (_, keep_alive_id) = *self.keep_alive_ids.get_or_insert_with(keep_alive_ids);
+        if let Some((id, len)) = decode_varint(packet) {
+            self.sent_by_id.entry(id).or_default().add(packet.len());
+            if Some(id) == keep_alive_id {
+                if let Some((keep_alive, received_at)) = self.pending_keep_alive.take() {
+                    if keep_alive == packet[len..] {
+                        self.keep_alive_turnaround = Some(received_at.elapsed());
+                    }
+                }
+            }
+        }
+    }
+
+    fn record_keep_alive(&mut self, keep_alive_id: i64, received_at: SystemTime) {
+        let received_at = match received_at.duration_since(UNIX_EPOCH) {
+            Ok(received_at) => received_at.as_millis() as i64,
+            Err(_) => return,
+        };
+        // Ids that don't look like a recent timestamp are ignored
+        let delay = received_at - keep_alive_id;
+        if !(0..60_000).contains(&delay) {
+            return;
+        }
+        let delay = Duration::from_millis(delay as u64);
+        self.latency = Some(match self.latency {
+            Some(latency) => (latency * 3 + delay) / 4,
+            None => delay,
+        });
+    }
+
+    pub(crate) fn record_update_time(&mut self, packet_id: i32, time: Duration) {
+        let handling_time = self.update_time_by_id.entry(packet_id).or_default();
+        handling_time.count += 1;
+        handling_time.total += time;
+        handling_time.max = handling_time.max.max(time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keep_alive(id: i32, value: u8) -> Vec<u8> {
+        let mut packet = vec![id as u8];
+        packet.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, value]);
+        packet
+    }
+
+    #[test]
+    fn test_counters() {
+        let mut stats = NetworkStats::default();
+        stats.record_received(&[0x22, 1, 2]);
+        stats.record_received(&[0x22]);
+        stats.record_sent(&[0x05, 1]);
+        assert_eq!(stats.received.packets, 2);
+        assert_eq!(stats.received.bytes, 4);
+        assert_eq!(stats.received_by_id[&0x22].packets, 2);
+        assert_eq!(stats.sent.bytes, 2);
+        assert_eq!(stats.sent_by_id[&0x05].packets, 1);
+    }
+
+    #[test]
+    fn test_keep_alive_turnaround() {
+        let (clientbound, serverbound) = keep_alive_ids();
+        let (clientbound, serverbound) = (clientbound.unwrap(), serverbound.unwrap());
+        let mut stats = NetworkStats::default();
+
+        // A different keep alive is not an answer
+        stats.record_received(&keep_alive(clientbound, 1));
+        stats.record_sent(&keep_alive(serverbound, 2));
+        assert!(stats.keep_alive_turnaround.is_none());
+
+        stats.record_received(&keep_alive(clientbound, 3));
+        stats.record_sent(&keep_alive(serverbound, 3));
+        assert!(stats.keep_alive_turnaround.is_some());
+    }
+
+    #[test]
+    fn test_latency() {
+        let (clientbound, _) = keep_alive_ids();
+        let mut stats = NetworkStats::default();
+        let sent_at = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+
+        // Ids that are not timestamps
+        stats.record_keep_alive(42, sent_at);
+        stats.record_keep_alive(sent_at.duration_since(UNIX_EPOCH).unwrap().as_millis() as i64 + 100, sent_at);
+        assert!(stats.latency.is_none());
+
+        let sent_at_ms = sent_at.duration_since(UNIX_EPOCH).unwrap().as_millis() as i64;
+        stats.record_keep_alive(sent_at_ms, sent_at + Duration::from_millis(100));
+        assert_eq!(stats.latency, Some(Duration::from_millis(100)));
+        stats.record_keep_alive(sent_at_ms, sent_at + Duration::from_millis(20));
+        assert_eq!(stats.latency, Some(Duration::from_millis(80)));
+
+        // Keep alives are measured when they come out of the socket
+        let mut packet = vec![clientbound.unwrap() as u8];
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as i64;
+        packet.extend_from_slice(&now.to_be_bytes());
+        let mut stats = NetworkStats::default();
+        stats.record_received(&packet);
+        assert!(stats.latency.unwrap() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_handling_time() {
+        let mut stats = NetworkStats::default();
+        stats.record_update_time(1, Duration::from_millis(2));
+        stats.record_update_time(1, Duration::from_millis(4));
+        assert_eq!(stats.update_time_by_id[&1].average(), Duration::from_millis(3));
+        assert_eq!(stats.update_time_by_id[&1].max, Duration::from_millis(4));
+    }
+}
@@ -0,0 +1,119 @@
+use log::*;
+use minecraft_protocol::{network::*, *};
+use super::{open_stream, Proxy};
+use crate::chat::ChatComponent;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerVersion {
+    pub name: String,
+    pub protocol: i32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlayerSample {
+    pub name: String,
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerPlayers {
+    pub max: u32,
+    pub online: u32,
+    #[serde(default)]
+    pub sample: Vec<PlayerSample>,
+}
+
+/// The response to a server list ping.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerStatus {
+    pub version: ServerVersion,
+    pub players: ServerPlayers,
+    /// The MOTD, as a raw chat component
+    #[serde(default)]
+    pub description: serde_json::Value,
+    #[serde(skip)]
+    pub latency: Duration,
+}
+
+impl ServerStatus {
+    /// The MOTD as plain text
+    pub fn motd(&self) -> String {
+        ChatComponent::from_json(&self.description).to_plain()
+    }
+
+    pub fn player_names(&self) -> Vec<&str> {
+        self.players.sample.iter().map(|p| p.name.as_str()).collect()
+    }
+}
+
+/// Queries the status of a server like the server list of the vanilla client does.
+pub fn ping(addr: &str, port: u16) -> Result<ServerStatus, String> {
+    ping_through(addr, port, None)
+}
+
+/// Same as [ping] but optionally through a proxy.
+pub fn ping_through(addr: &str, port: u16, proxy: Option<&Proxy>) -> Result<ServerStatus, String> {
+    let mut stream = open_stream(addr, port, proxy)?;
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .map_err(|e| format!("Failed to set timeout: {}", e))?;
+
+    send_packet(
+        &mut stream,
+        minecraft_protocol::packets::handshake::ServerboundPacket::Hello {
+            protocol_version: super::PROTOCOL_VERSION.into(),
+            server_address: addr,
+            server_port: port,
+            next_state: minecraft_protocol::packets::ConnectionState::Status,
+        }
+        .serialize_minecraft_packet()
+        .unwrap(),
+        None,
+        None,
+    )
+    .map_err(|e| format!("Failed to send handshake: {:?}", e))?;
+
+    send_packet(
+        &mut stream,
+        minecraft_protocol::packets::status::ServerboundPacket::Request {}.serialize_minecraft_packet().unwrap(),
+        None,
+        None,
+    )
+    .map_err(|e| format!("Failed to send status request: {:?}", e))?;
+
+    let response = read_packet(&mut stream, None, None).map_err(|e| format!("Failed to read status response: {:?}", e))?;
+    let mut status: ServerStatus = match minecraft_protocol::packets::status::ClientboundPacket::deserialize_uncompressed_minecraft_packet(&response) {
+        Ok(minecraft_protocol::packets::status::ClientboundPacket::Response { json_response }) => {
+            serde_json::from_str(json_response).map_err(|e| format!("Invalid status json: {}", e))?
+        }
+        Ok(packet) => return Err(format!("Unexpected status packet {:?}", packet)),
+        Err(e) => return Err(format!("Failed to parse status response: {:?}", e)),
+    };
+
+    let start_time = Instant::now();
+    send_packet(
+        &mut stream,
+        minecraft_protocol::packets::status::ServerboundPacket::Ping { payload: 42 }
+            .serialize_minecraft_packet()
+            .unwrap(),
+        None,
+        None,
+    )
+    .map_err(|e| format!("Failed to send ping: {:?}", e))?;
+    let response = read_packet(&mut stream, None, None).map_err(|e| format!("Failed to read pong: {:?}", e))?;
+    match minecraft_protocol::packets::status::ClientboundPacket::deserialize_uncompressed_minecraft_packet(&response) {
+        Ok(minecraft_protocol::packets::status::ClientboundPacket::Pong { payload: 42 }) => (),
+        Ok(packet) => return Err(format!("Unexpected pong packet {:?}", packet)),
+        Err(e) => return Err(format!("Failed to parse pong: {:?}", e)),
+    }
+    status.latency = start_time.elapsed();
+
+    debug!(
+        "{}:{} runs {} (protocol {}) with {}/{} players, latency {:?}",
+        addr, port, status.version.name, status.version.protocol, status.players.online, status.players.max, status.latency
+    );
+
+    Ok(status)
+}
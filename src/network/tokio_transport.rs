@@ -0,0 +1,190 @@
+//! An alternative to the two network threads per bot, so that a single process can host many bots.
+
+use super::*;
+use cfb8::cipher::AsyncStreamCipher;
+use log::*;
+use std::{
+    io::Cursor,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::tcp::{OwnedReadHalf, OwnedWriteHalf},
+    sync::{mpsc as async_mpsc, Notify},
+    task::JoinHandle,
+};
+
+fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+/// Reads a whole frame asynchronously, then parses it with the same code as the blocking transport.
+async fn read_packet_async(stream: &mut OwnedReadHalf, cipher: &mut Option<AesCfb8>, compression: Option<u32>) -> std::io::Result<Vec<u8>> {
+    // The length prefix has to be read byte by byte since its size is unknown
+    let mut frame = Vec::with_capacity(5);
+    loop {
+        let mut byte = [stream.read_u8().await?];
+        if let Some(cipher) = cipher.as_mut() {
+            cipher.decrypt(&mut byte);
+        }
+        frame.push(byte[0]);
+        if byte[0] & 0x80 == 0 || frame.len() >= 5 {
+            break;
+        }
+    }
+    let (length, _) = decode_varint(&frame).ok_or_else(|| invalid_data("Invalid packet length".to_string()))?;
+    if !(0..=MAX_PACKET_LENGTH).contains(&length) {
+        return Err(invalid_data(format!("Invalid packet length {}", length)));
+    }
+
+    let header_length = frame.len();
+    frame.resize(header_length + length as usize, 0);
+    stream.read_exact(&mut frame[header_length..]).await?;
+    if let Some(cipher) = cipher.as_mut() {
+        cipher.decrypt(&mut frame[header_length..]);
+    }
+
+    read_packet(&mut Cursor::new(frame), compression, None).map_err(|e| invalid_data(format!("{:?}", e)))
+}
+
+/// Frames the packet with the same code as the blocking transport, then writes it asynchronously.
+async fn send_packet_async(stream: &mut OwnedWriteHalf, cipher: &mut Option<AesCfb8>, compression: Option<u32>, packet: Vec<u8>) -> std::io::Result<()> {
+    let mut frame = Vec::with_capacity(packet.len() + 10);
+    send_packet(&mut frame, packet, compression, None).map_err(|e| invalid_data(format!("{:?}", e)))?;
+    if let Some(cipher) = cipher.as_mut() {
+        cipher.encrypt(&mut frame);
+    }
+    stream.write_all(&frame).await
+}
+
+async fn receive_packets_async(
+    hidden_sender: async_mpsc::UnboundedSender<ConnectionEvent>,
+    mut stream: OwnedReadHalf,
+    mut cipher: Option<AesCfb8>,
+    compression: Option<u32>,
+    running: Arc<AtomicBool>,
+    recorder: Option<Arc<Mutex<PacketRecorder>>>,
+    stats: Arc<Mutex<NetworkStats>>,
+) {
+    loop {
+        let packet = match read_packet_async(&mut stream, &mut cipher, compression).await {
+            Ok(packet) => packet,
+            Err(e) => {
+                let state = match running.swap(false, Ordering::SeqCst) {
+                    true => ConnectionState::Errored(format!("Failed to read packet: {}", e)),
+                    false => ConnectionState::Disconnected("Connection closed by the client".to_string()),
+                };
+                let _ = hidden_sender.send(ConnectionEvent::Closed(state));
+                break;
+            }
+        };
+        record(&recorder, PacketDirection::Clientbound, &packet);
+        stats.lock().unwrap().record_received(&packet);
+        if hidden_sender.send(ConnectionEvent::Packet(packet)).is_err() {
+            running.store(false, Ordering::SeqCst);
+            break;
+        }
+    }
+    trace!("Receiving task stopped");
+}
+
+async fn send_packets_async(
+    mut hidden_receiver: async_mpsc::UnboundedReceiver<(PacketPriority, Vec<u8>)>,
+    closed: Arc<Notify>,
+    mut stream: OwnedWriteHalf,
+    mut cipher: Option<AesCfb8>,
+    compression: Option<u32>,
+    running: Arc<AtomicBool>,
+    events: async_mpsc::UnboundedSender<ConnectionEvent>,
+    recorder: Option<Arc<Mutex<PacketRecorder>>>,
+    stats: Arc<Mutex<NetworkStats>>,
+    mut scheduler: PacketScheduler,
+    receiving_task: JoinHandle<()>,
+) {
+    'sending: while running.load(Ordering::SeqCst) {
+        while let Some(packet) = scheduler.pop_ready(Instant::now()) {
+            trace!("Sending {:?}", packet);
+            record(&recorder, PacketDirection::Serverbound, &packet);
+            stats.lock().unwrap().record_sent(&packet);
+            if let Err(e) = send_packet_async(&mut stream, &mut cipher, compression, packet).await {
+                if running.swap(false, Ordering::SeqCst) {
+                    let _ = events.send(ConnectionEvent::Closed(ConnectionState::Errored(format!("Failed to send packet: {}", e))));
+                }
+                break 'sending;
+            }
+        }
+
+        // Sleep until a packet is queued, a rate limited packet can be sent or the connection is closed
+        let rate_limited = scheduler.next_ready_in();
+        tokio::select! {
+            message = hidden_receiver.recv() => match message {
+                Some((priority, packet)) => scheduler.push(priority, packet),
+                None => {
+                    running.store(false, Ordering::SeqCst);
+                    break 'sending;
+                }
+            },
+            _ = tokio::time::sleep(rate_limited.unwrap_or_default()), if rate_limited.is_some() => (),
+            _ = closed.notified() => (),
+        }
+    }
+    let _ = stream.shutdown().await;
+    // The receiving task would wait for the server to close the connection
+    receiving_task.abort();
+    // Only the first Closed event is taken into account, so this is ignored if the receiving task already reported an error
+    let _ = events.send(ConnectionEvent::Closed(ConnectionState::Disconnected("Connection closed by the client".to_string())));
+    trace!("Sending task stopped");
+}
+
+/// Same as [connect] but the connection is driven by tokio tasks instead of two threads.
+/// The login sequence is still blocking and runs on the blocking thread pool.
+pub async fn connect_async(
+    addr: String,
+    port: u16,
+    username: String,
+    options: ConnectionOptions,
+    stats: Arc<Mutex<NetworkStats>>,
+) -> Result<(async_mpsc::UnboundedReceiver<ConnectionEvent>, PacketSender), ConnectionState> {
+    let recorder = options.recorder.clone();
+    let scheduler = PacketScheduler::new(&options.rate_limits);
+    let recorder2 = options.recorder.clone();
+    let LoggedInConnection { reader, writer, compression } = tokio::task::spawn_blocking(move || login(&addr, port, &username, &options))
+        .await
+        .map_err(|e| ConnectionState::Errored(format!("Login task failed: {}", e)))??;
+
+    let (stream, read_cipher) = reader.into_parts();
+    let (_, write_cipher) = writer.into_parts();
+    stream
+        .set_nonblocking(true)
+        .map_err(|e| ConnectionState::Errored(format!("Failed to make the socket non blocking: {}", e)))?;
+    let stream = tokio::net::TcpStream::from_std(stream).map_err(|e| ConnectionState::Errored(format!("Failed to register the socket: {}", e)))?;
+    let (read_half, write_half) = stream.into_split();
+
+    let (hidden_sender, receiver) = async_mpsc::unbounded_channel::<ConnectionEvent>();
+    let (sender, hidden_receiver, closed) = PacketSender::async_channel();
+    let running = sender.running();
+    let running2 = Arc::clone(&running);
+    let events = hidden_sender.clone();
+    let stats2 = Arc::clone(&stats);
+
+    let receiving_task = tokio::spawn(receive_packets_async(hidden_sender, read_half, read_cipher, compression, running, recorder, stats));
+    tokio::spawn(send_packets_async(
+        hidden_receiver,
+        closed,
+        write_half,
+        write_cipher,
+        compression,
+        running2,
+        events,
+        recorder2,
+        stats2,
+        scheduler,
+        receiving_task,
+    ));
+
+    Ok((receiver, sender))
+}
@@ -1,4 +1,4 @@
-use crate::map::Map;
+use crate::{map::Map, physics::is_climbable};
 use log::*;
 use std::collections::BinaryHeap;
 
@@ -35,9 +35,18 @@ impl Node {
         self.g_cost + self.h_cost
     }
 
+    /// Whether the bot can stand in a block
+    #[inline]
+    fn is_passable(map: &Map, x: i32, y: i32, z: i32) -> bool {
+        let block = map.get_block(x, y, z);
+        block.is_air_block() || is_climbable(block)
+    }
+
     #[inline]
     fn check_direct_neighbor(map: &Map, x: i32, ay: i32, z: i32) -> bool {
-        map.get_block(x, ay, z).is_air_block() && map.get_block(x, ay + 1, z).is_air_block() && map.get_block(x, ay - 1, z).is_blocking()
+        Node::is_passable(map, x, ay, z)
+            && Node::is_passable(map, x, ay + 1, z)
+            && (map.get_block(x, ay - 1, z).is_blocking() || is_climbable(map.get_block(x, ay, z)))
     }
 
     #[inline]
@@ -94,7 +103,21 @@ impl Node {
             false
         };
 
+        // Ladders, vines and scaffolding can be climbed up and down
+        let add_vertical_neighbors = |open_nodes: &mut BinaryHeap<Node>| {
+            if is_climbable(map.get_block(self.x, self.y, self.z))
+                && Node::is_passable(map, self.x, self.y + 1, self.z)
+                && Node::is_passable(map, self.x, self.y + 2, self.z)
+            {
+                open_nodes.push(Node::new((self.x, self.y + 1, self.z), (self.x, self.y, self.z), destination, self.g_cost + 10));
+            }
+            if is_climbable(map.get_block(self.x, self.y - 1, self.z)) {
+                open_nodes.push(Node::new((self.x, self.y - 1, self.z), (self.x, self.y, self.z), destination, self.g_cost + 10));
+            }
+        };
+
         let on = open_nodes;
+        add_vertical_neighbors(on);
         add_direct_neighbor(self.x + 1, self.z, on) || add_uphill_neighbor(self.x + 1, self.z, on) || add_downhill_neighbors(self.x + 1, self.z, on);
         add_direct_neighbor(self.x - 1, self.z, on) || add_uphill_neighbor(self.x - 1, self.z, on) || add_downhill_neighbors(self.x - 1, self.z, on);
         add_direct_neighbor(self.x, self.z + 1, on) || add_uphill_neighbor(self.x, self.z + 1, on) || add_downhill_neighbors(self.x, self.z + 1, on);
@@ -0,0 +1,304 @@
+use super::Aabb;
+use crate::*;
+
+/// State ids of the water and lava blocks in 1.17.1, one per level
+const WATER_STATES: std::ops::RangeInclusive<u32> = 34..=49;
+const LAVA_STATES: std::ops::RangeInclusive<u32> = 50..=65;
+
+/// How much a current pushes the entities in it, per tick
+const WATER_PUSH: f64 = 0.014;
+const LAVA_PUSH: f64 = 0.007 / 3.0;
+/// The weakest push of a current on an entity that is nearly still, so that it does not get stuck
+const MIN_PUSH: f64 = 0.0045;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fluid {
+    Water,
+    Lava,
+}
+
+/// The fluid contained in a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FluidState {
+    pub fluid: Fluid,
+    /// 0 for a source, 1 to 7 for flowing fluid getting thinner, 8 and more for falling fluid
+    pub level: u8,
+}
+
+impl FluidState {
+    /// Reads the fluid of a block.
+    /// Waterlogged blocks are not detected since their water is a property of the block state.
+    pub fn at(map: &Map, x: i32, y: i32, z: i32) -> Option<FluidState> {
+        let state = map.get_block_state(x, y, z);
+        if WATER_STATES.contains(&state) {
+            return Some(FluidState {
+                fluid: Fluid::Water,
+                level: (state - WATER_STATES.start()) as u8,
+            });
+        }
+        if LAVA_STATES.contains(&state) {
+            return Some(FluidState {
+                fluid: Fluid::Lava,
+                level: (state - LAVA_STATES.start()) as u8,
+            });
+        }
+        match Block::from_state_id(state) {
+            Some(Block::Seagrass) | Some(Block::TallSeagrass) | Some(Block::Kelp) | Some(Block::KelpPlant) | Some(Block::BubbleColumn) => {
+                Some(FluidState { fluid: Fluid::Water, level: 0 })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_falling(&self) -> bool {
+        self.level >= 8
+    }
+
+    /// The height of the fluid in its block, ignoring the fluid above
+    pub fn own_height(&self) -> f64 {
+        let amount = match self.level {
+            level if level >= 8 => 8,
+            level => 8 - level,
+        };
+        amount as f64 / 9.0
+    }
+
+    /// The height of the surface of the fluid in its block
+    pub fn height(&self, map: &Map, x: i32, y: i32, z: i32) -> f64 {
+        match FluidState::at(map, x, y + 1, z) {
+            Some(above) if above.fluid == self.fluid => 1.0,
+            _ => self.own_height(),
+        }
+    }
+
+    /// The direction of the current in a fluid block, normalized
+    pub fn flow(&self, map: &Map, x: i32, y: i32, z: i32) -> (f64, f64, f64) {
+        let own_height = self.own_height();
+        let (mut flow_x, mut flow_y, mut flow_z) = (0.0, 0.0, 0.0);
+        let mut next_to_wall = false;
+
+        for (dx, dz) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+            let (nx, nz) = (x + dx, z + dz);
+            let neighbor = FluidState::at(map, nx, y, nz);
+            let difference = match neighbor {
+                Some(neighbor) if neighbor.fluid == self.fluid => own_height - neighbor.own_height(),
+                Some(_) => continue,
+                None if map.get_block(nx, y, nz).is_blocking() => {
+                    next_to_wall = true;
+                    continue;
+                }
+                // The fluid flows towards the holes it falls in
+                None => match FluidState::at(map, nx, y - 1, nz) {
+                    Some(below) if below.fluid == self.fluid => own_height - (below.own_height() - 0.888_888_9),
+                    _ => continue,
+                },
+            };
+            flow_x += dx as f64 * difference;
+            flow_z += dz as f64 * difference;
+        }
+
+        if self.is_falling() && next_to_wall {
+            let (x, _, z) = normalize((flow_x, 0.0, flow_z));
+            flow_x = x;
+            flow_y = -6.0;
+            flow_z = z;
+        }
+        normalize((flow_x, flow_y, flow_z))
+    }
+}
+
+fn normalize((x, y, z): (f64, f64, f64)) -> (f64, f64, f64) {
+    let length = (x * x + y * y + z * z).sqrt();
+    if length < 1.0e-4 {
+        return (0.0, 0.0, 0.0);
+    }
+    (x / length, y / length, z / length)
+}
+
+/// How deep an entity is in fluids, and how the currents push it
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FluidContact {
+    /// How far above the feet the water goes, 0 if out of water
+    pub water_depth: f64,
+    pub lava_depth: f64,
+    /// The average current of the water blocks touched, weaker in shallow water
+    pub water_flow: (f64, f64, f64),
+    pub lava_flow: (f64, f64, f64),
+}
+
+impl FluidContact {
+    /// Checks the fluids touching a hitbox
+    pub fn of(map: &Map, hitbox: &Aabb) -> FluidContact {
+        let hitbox = Aabb::new(
+            hitbox.min_x + 0.001,
+            hitbox.min_y + 0.001,
+            hitbox.min_z + 0.001,
+            hitbox.max_x - 0.001,
+            hitbox.max_y - 0.001,
+            hitbox.max_z - 0.001,
+        );
+        let mut contact = FluidContact::default();
+        let mut water_blocks = 0;
+        let mut lava_blocks = 0;
+
+        for x in (hitbox.min_x.floor() as i32)..=(hitbox.max_x.floor() as i32) {
+            for y in (hitbox.min_y.floor() as i32)..=(hitbox.max_y.floor() as i32) {
+                for z in (hitbox.min_z.floor() as i32)..=(hitbox.max_z.floor() as i32) {
+                    let state = match FluidState::at(map, x, y, z) {
+                        Some(state) => state,
+                        None => continue,
+                    };
+                    let surface = y as f64 + state.height(map, x, y, z);
+                    if surface < hitbox.min_y {
+                        continue;
+                    }
+                    let depth = surface - hitbox.min_y;
+                    let (mut flow_x, mut flow_y, mut flow_z) = state.flow(map, x, y, z);
+                    // Shallow fluids push less
+                    if depth < 0.4 {
+                        flow_x *= depth;
+                        flow_y *= depth;
+                        flow_z *= depth;
+                    }
+                    let (total_depth, total_flow, blocks) = match state.fluid {
+                        Fluid::Water => (&mut contact.water_depth, &mut contact.water_flow, &mut water_blocks),
+                        Fluid::Lava => (&mut contact.lava_depth, &mut contact.lava_flow, &mut lava_blocks),
+                    };
+                    *total_depth = total_depth.max(depth);
+                    total_flow.0 += flow_x;
+                    total_flow.1 += flow_y;
+                    total_flow.2 += flow_z;
+                    *blocks += 1;
+                }
+            }
+        }
+
+        // Being in several blocks of a current does not make it stronger
+        for (flow, blocks) in [(&mut contact.water_flow, water_blocks), (&mut contact.lava_flow, lava_blocks)] {
+            if blocks > 0 {
+                let blocks = blocks as f64;
+                *flow = (flow.0 / blocks, flow.1 / blocks, flow.2 / blocks);
+            }
+        }
+        contact
+    }
+
+    /// The velocity to add because of the currents, given the horizontal velocity of the entity
+    pub fn push(&self, velocity_x: f64, velocity_z: f64) -> (f64, f64, f64) {
+        let nearly_still = velocity_x.abs() < 0.003 && velocity_z.abs() < 0.003;
+        let mut push = (0.0, 0.0, 0.0);
+        for (flow, strength) in [(self.water_flow, WATER_PUSH), (self.lava_flow, LAVA_PUSH)] {
+            let length = (flow.0 * flow.0 + flow.1 * flow.1 + flow.2 * flow.2).sqrt();
+            if length == 0.0 {
+                continue;
+            }
+            let scale = match nearly_still && length * strength < MIN_PUSH {
+                true => MIN_PUSH / length,
+                false => strength,
+            };
+            push.0 += flow.0 * scale;
+            push.1 += flow.1 * scale;
+            push.2 += flow.2 * scale;
+        }
+        push
+    }
+
+    pub fn in_water(&self) -> bool {
+        self.water_depth > 0.0
+    }
+
+    pub fn in_lava(&self) -> bool {
+        self.lava_depth > 0.0
+    }
+}
+
+/// Whether there is any fluid in an area
+pub fn contains_fluid(map: &Map, area: &Aabb) -> bool {
+    for x in (area.min_x.floor() as i32)..=(area.max_x.floor() as i32) {
+        for y in (area.min_y.floor() as i32)..=(area.max_y.floor() as i32) {
+            for z in (area.min_z.floor() as i32)..=(area.max_z.floor() as i32) {
+                if let Some(state) = FluidState::at(map, x, y, z) {
+                    if y as f64 + state.height(map, x, y, z) > area.min_y {
+                        return true;
+                    }
+                }
+            }
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn water(level: u32) -> u32 {
+        WATER_STATES.start() + level
+    }
+
+    /// A stone floor at y=0 and a current going east at y=1: a source at x=8, then thinner water until x=11
+    fn river() -> Map {
+        let mut map = Map::new();
+        map.load_empty_chunk(0, 0);
+        for x in 0..16 {
+            for z in 0..16 {
+                map.set_block(x, 0, z, Block::Stone);
+            }
+        }
+        for level in 0..4 {
+            map.set_block_state(8 + level as i32, 1, 8, water(level));
+        }
+        map
+    }
+
+    fn assert_close(value: (f64, f64, f64), expected: (f64, f64, f64)) {
+        let difference = (value.0 - expected.0).abs() + (value.1 - expected.1).abs() + (value.2 - expected.2).abs();
+        assert!(difference < 1.0e-9, "{:?} is not {:?}", value, expected);
+    }
+
+    #[test]
+    fn test_fluid_states() {
+        let mut map = river();
+        map.set_block_state(8, 2, 8, water(8));
+        map.set_block_state(5, 1, 5, LAVA_STATES.start() + 2);
+        let source = FluidState::at(&map, 8, 1, 8).unwrap();
+        assert_eq!(source, FluidState { fluid: Fluid::Water, level: 0 });
+        assert_eq!(source.height(&map, 8, 1, 8), 1.0);
+        let falling = FluidState::at(&map, 8, 2, 8).unwrap();
+        assert!(falling.is_falling());
+        assert_eq!(FluidState::at(&map, 9, 1, 8).unwrap().height(&map, 9, 1, 8), 7.0 / 9.0);
+        assert_eq!(FluidState::at(&map, 5, 1, 5), Some(FluidState { fluid: Fluid::Lava, level: 2 }));
+        assert_eq!(FluidState::at(&map, 5, 1, 6), None);
+    }
+
+    #[test]
+    fn test_flow() {
+        let map = river();
+        assert_close(FluidState::at(&map, 9, 1, 8).unwrap().flow(&map, 9, 1, 8), (1.0, 0.0, 0.0));
+        assert_close(FluidState::at(&map, 10, 1, 8).unwrap().flow(&map, 10, 1, 8), (1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_push_is_averaged() {
+        let map = river();
+        let one_block = FluidContact::of(&map, &Aabb::player(9.5, 1.0, 8.5));
+        let two_blocks = FluidContact::of(&map, &Aabb::player(10.0, 1.0, 8.5));
+        assert!(one_block.in_water() && !one_block.in_lava());
+        assert_close(one_block.water_flow, (1.0, 0.0, 0.0));
+        assert_close(two_blocks.water_flow, (1.0, 0.0, 0.0));
+        assert_close(two_blocks.push(0.1, 0.0), (WATER_PUSH, 0.0, 0.0));
+        assert!((one_block.water_depth - 7.0 / 9.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_minimum_push() {
+        let map = river();
+        // Shallow water pushes less
+        let contact = FluidContact::of(&map, &Aabb::player(9.5, 1.5, 8.5));
+        let (push, _, _) = contact.push(0.1, 0.0);
+        assert!(push > 0.0 && push < MIN_PUSH);
+        // Unless the entity is nearly still
+        assert_close(contact.push(0.0, 0.001), (MIN_PUSH, 0.0, 0.0));
+        assert_close(FluidContact::default().push(0.0, 0.0), (0.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,700 @@
+use crate::*;
+
+mod fluids;
+mod shapes;
+pub use fluids::*;
+pub use shapes::*;
+
+/// The size of the player hitbox
+pub const PLAYER_WIDTH: f64 = 0.6;
+pub const PLAYER_HEIGHT: f64 = 1.8;
+/// The height of the blocks the player can walk on without jumping
+pub const STEP_HEIGHT: f64 = 0.6;
+pub const GRAVITY: f64 = 0.08;
+pub const JUMP_VELOCITY: f64 = 0.42;
+/// The base value of the movement speed attribute of players
+pub const WALKING_SPEED: f64 = 0.1;
+/// The height of the eyes of a standing player
+pub const EYE_HEIGHT: f64 = 1.62;
+
+/// An axis-aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub min_z: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+    pub max_z: f64,
+}
+
+impl Aabb {
+    pub fn new(min_x: f64, min_y: f64, min_z: f64, max_x: f64, max_y: f64, max_z: f64) -> Aabb {
+        Aabb {
+            min_x,
+            min_y,
+            min_z,
+            max_x,
+            max_y,
+            max_z,
+        }
+    }
+
+    /// The hitbox of a player standing at the given position
+    pub fn player(x: f64, y: f64, z: f64) -> Aabb {
+        let half_width = PLAYER_WIDTH / 2.0;
+        Aabb::new(x - half_width, y, z - half_width, x + half_width, y + PLAYER_HEIGHT, z + half_width)
+    }
+
+    pub fn offset(&self, dx: f64, dy: f64, dz: f64) -> Aabb {
+        Aabb::new(self.min_x + dx, self.min_y + dy, self.min_z + dz, self.max_x + dx, self.max_y + dy, self.max_z + dz)
+    }
+
+    /// Extends the box in the direction of a movement
+    pub fn stretch(&self, dx: f64, dy: f64, dz: f64) -> Aabb {
+        Aabb::new(
+            self.min_x + dx.min(0.0),
+            self.min_y + dy.min(0.0),
+            self.min_z + dz.min(0.0),
+            self.max_x + dx.max(0.0),
+            self.max_y + dy.max(0.0),
+            self.max_z + dz.max(0.0),
+        )
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min_x < other.max_x
+            && self.max_x > other.min_x
+            && self.min_y < other.max_y
+            && self.max_y > other.min_y
+            && self.min_z < other.max_z
+            && self.max_z > other.min_z
+    }
+
+    /// How far `other` can move along X before hitting this box, up to `dx`
+    pub fn clip_x(&self, other: &Aabb, dx: f64) -> f64 {
+        if other.max_y <= self.min_y || other.min_y >= self.max_y || other.max_z <= self.min_z || other.min_z >= self.max_z {
+            return dx;
+        }
+        if dx > 0.0 && other.max_x <= self.min_x {
+            dx.min(self.min_x - other.max_x)
+        } else if dx < 0.0 && other.min_x >= self.max_x {
+            dx.max(self.max_x - other.min_x)
+        } else {
+            dx
+        }
+    }
+
+    /// How far `other` can move along Y before hitting this box, up to `dy`
+    pub fn clip_y(&self, other: &Aabb, dy: f64) -> f64 {
+        if other.max_x <= self.min_x || other.min_x >= self.max_x || other.max_z <= self.min_z || other.min_z >= self.max_z {
+            return dy;
+        }
+        if dy > 0.0 && other.max_y <= self.min_y {
+            dy.min(self.min_y - other.max_y)
+        } else if dy < 0.0 && other.min_y >= self.max_y {
+            dy.max(self.max_y - other.min_y)
+        } else {
+            dy
+        }
+    }
+
+    /// How far `other` can move along Z before hitting this box, up to `dz`
+    pub fn clip_z(&self, other: &Aabb, dz: f64) -> f64 {
+        if other.max_x <= self.min_x || other.min_x >= self.max_x || other.max_y <= self.min_y || other.min_y >= self.max_y {
+            return dz;
+        }
+        if dz > 0.0 && other.max_z <= self.min_z {
+            dz.min(self.min_z - other.max_z)
+        } else if dz < 0.0 && other.min_z >= self.max_z {
+            dz.max(self.max_z - other.min_z)
+        } else {
+            dz
+        }
+    }
+}
+
+/// How much a block keeps of the horizontal speed of the entities sliding on it
+pub fn block_slipperiness(block: Block) -> f64 {
+    match block {
+        Block::Ice | Block::PackedIce | Block::FrostedIce => 0.98,
+        Block::BlueIce => 0.989,
+        Block::SlimeBlock => 0.8,
+        _ => 0.6,
+    }
+}
+
+/// Whether entities inside a block can climb it
+pub fn is_climbable(block: Block) -> bool {
+    matches!(
+        block,
+        Block::Ladder
+            | Block::Vine
+            | Block::Scaffolding
+            | Block::TwistingVines
+            | Block::TwistingVinesPlant
+            | Block::WeepingVines
+            | Block::WeepingVinesPlant
+            | Block::CaveVines
+            | Block::CaveVinesPlant
+    )
+}
+
+/// Multiplier applied to the horizontal speed of the entities walking in or on a block
+pub fn block_speed_factor(block: Block) -> f64 {
+    match block {
+        Block::SoulSand | Block::HoneyBlock => 0.4,
+        _ => 1.0,
+    }
+}
+
+/// The collision boxes of the blocks intersecting an area, for an entity in the given context.
+/// Fences and walls are 1.5 blocks high, so the blocks below the area are checked too.
+pub fn collision_boxes(map: &Map, area: &Aabb, context: &CollisionContext) -> Vec<Aabb> {
+    let mut boxes = Vec::new();
+    for y in (area.min_y.floor() as i32 - 1)..=(area.max_y.floor() as i32) {
+        for x in (area.min_x.floor() as i32)..=(area.max_x.floor() as i32) {
+            for z in (area.min_z.floor() as i32)..=(area.max_z.floor() as i32) {
+                for block_box in contextual_collision_boxes(map.get_block_state(x, y, z), y, context) {
+                    let block_box = block_box.offset(x as f64, y as f64, z as f64);
+                    if block_box.intersects(area) {
+                        boxes.push(block_box);
+                    }
+                }
+            }
+        }
+    }
+    boxes
+}
+
+/// Moves a box as far as possible without going through any of the obstacles.
+/// Returns the movement that was actually made.
+fn clip_movement(obstacles: &[Aabb], mut hitbox: Aabb, mut dx: f64, mut dy: f64, mut dz: f64) -> (f64, f64, f64) {
+    for obstacle in obstacles {
+        dy = obstacle.clip_y(&hitbox, dy);
+    }
+    hitbox = hitbox.offset(0.0, dy, 0.0);
+    for obstacle in obstacles {
+        dx = obstacle.clip_x(&hitbox, dx);
+    }
+    hitbox = hitbox.offset(dx, 0.0, 0.0);
+    for obstacle in obstacles {
+        dz = obstacle.clip_z(&hitbox, dz);
+    }
+    (dx, dy, dz)
+}
+
+/// What the bot wants to do, like the keys pressed by a player.
+/// Missions set it at every tick and it is reset before the next one.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MovementInput {
+    /// From -1 (backward) to 1 (forward), relative to the yaw
+    pub forward: f64,
+    /// From -1 (right) to 1 (left), relative to the yaw
+    pub strafe: f64,
+    pub jump: bool,
+    pub sprint: bool,
+    /// Slows down and prevents falling from edges
+    pub sneak: bool,
+}
+
+/// The movement state of the bot, simulated like the vanilla client does
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Physics {
+    pub velocity_x: f64,
+    pub velocity_y: f64,
+    pub velocity_z: f64,
+    pub on_ground: bool,
+    /// Whether the last movement was stopped by a wall
+    pub horizontal_collision: bool,
+    /// The fluids the bot was in at the beginning of the last tick
+    pub fluids: FluidContact,
+    /// Whether the bot is in a ladder, a vine or scaffolding
+    pub on_climbable: bool,
+    /// Whether the bot is sprinting under water, which makes it move where it looks
+    pub swimming: bool,
+    pub input: MovementInput,
+}
+
+impl Physics {
+    pub fn new() -> Physics {
+        Physics::default()
+    }
+
+    pub fn in_water(&self) -> bool {
+        self.fluids.in_water()
+    }
+
+    pub fn in_lava(&self) -> bool {
+        self.fluids.in_lava()
+    }
+
+    /// Whether the eyes of the bot are under water
+    pub fn is_underwater(&self) -> bool {
+        self.fluids.water_depth > EYE_HEIGHT
+    }
+
+    /// Forgets the current speed, after a teleportation for example
+    pub fn reset(&mut self) {
+        *self = Physics::default();
+    }
+
+    /// The block below the feet of an entity, which gives the friction
+    fn block_below(map: &Map, x: f64, y: f64, z: f64) -> Block {
+        map.get_block(x.floor() as i32, (y - 0.5).floor() as i32, z.floor() as i32)
+    }
+
+    fn feet_block(map: &Map, position: &PlayerPosition) -> Block {
+        map.get_block(position.x.floor() as i32, position.y.floor() as i32, position.z.floor() as i32)
+    }
+
+    /// Simulates a tick of movement and updates the position of the bot
+    pub fn tick(&mut self, map: &Map, position: &mut PlayerPosition) {
+        let input = self.input;
+
+        // Tiny speeds are cancelled
+        for velocity in [&mut self.velocity_x, &mut self.velocity_y, &mut self.velocity_z] {
+            if velocity.abs() < 0.003 {
+                *velocity = 0.0;
+            }
+        }
+
+        self.fluids = FluidContact::of(map, &Aabb::player(position.x, position.y, position.z));
+        let push = self.fluids.push(self.velocity_x, self.velocity_z);
+        self.velocity_x += push.0;
+        self.velocity_y += push.1;
+        self.velocity_z += push.2;
+        self.swimming = input.sprint && input.forward > 0.0 && match self.swimming {
+            true => self.in_water(),
+            false => self.is_underwater(),
+        };
+
+        let fluid_depth = self.fluids.water_depth.max(self.fluids.lava_depth);
+        if input.jump {
+            if fluid_depth > 0.0 && !(self.on_ground && fluid_depth <= 0.4) {
+                self.velocity_y += 0.04;
+            } else if self.on_ground {
+                self.velocity_y = JUMP_VELOCITY;
+                if input.sprint {
+                    let yaw = (position.yaw as f64).to_radians();
+                    self.velocity_x -= yaw.sin() * 0.2;
+                    self.velocity_z += yaw.cos() * 0.2;
+                }
+            }
+        }
+        if input.sneak && self.in_water() {
+            self.velocity_y -= 0.04;
+        }
+
+        self.on_climbable = is_climbable(Physics::feet_block(map, position));
+        if self.in_water() {
+            self.travel_in_water(map, position);
+        } else if self.in_lava() {
+            self.travel_in_lava(map, position);
+        } else {
+            self.travel_on_land(map, position);
+        }
+    }
+
+    fn travel_on_land(&mut self, map: &Map, position: &mut PlayerPosition) {
+        let input = self.input;
+        let slipperiness = match self.on_ground {
+            true => block_slipperiness(Physics::block_below(map, position.x, position.y, position.z)),
+            false => 1.0,
+        };
+        let movement_speed = WALKING_SPEED * if input.sprint { 1.3 } else { 1.0 };
+        let acceleration = match self.on_ground {
+            true => movement_speed * (0.216_000_02 / (slipperiness * slipperiness * slipperiness)),
+            false if input.sprint => 0.025_999_999,
+            false => 0.02,
+        };
+        let sneak_factor = if input.sneak { 0.3 } else { 1.0 };
+        self.accelerate(input.strafe * 0.98 * sneak_factor, input.forward * 0.98 * sneak_factor, acceleration, position.yaw);
+
+        if self.on_climbable {
+            // Climbable blocks slow down and stop falls
+            self.velocity_x = self.velocity_x.max(-0.15).min(0.15);
+            self.velocity_z = self.velocity_z.max(-0.15).min(0.15);
+            self.velocity_y = self.velocity_y.max(-0.15);
+            // Sneaking holds on ladders and vines, while the bot stands on scaffolding unless it sneaks to go down
+            let in_scaffolding = Physics::feet_block(map, position) == Block::Scaffolding;
+            if self.velocity_y < 0.0 && input.sneak && !in_scaffolding {
+                self.velocity_y = 0.0;
+            }
+        }
+
+        self.move_entity(map, position, input.sneak);
+
+        // Climbing is done by walking against the wall or jumping
+        if self.on_climbable && (self.horizontal_collision || input.jump) {
+            self.velocity_y = 0.2;
+        }
+
+        let friction = match self.on_ground {
+            true => block_slipperiness(Physics::block_below(map, position.x, position.y, position.z)) * 0.91,
+            false => 0.91,
+        };
+        self.velocity_y = (self.velocity_y - GRAVITY) * 0.98;
+        self.velocity_x *= friction;
+        self.velocity_z *= friction;
+    }
+
+    fn travel_in_water(&mut self, map: &Map, position: &mut PlayerPosition) {
+        let input = self.input;
+        if self.swimming {
+            // Swimming goes where the bot looks
+            let look_y = -(position.pitch as f64).to_radians().sin();
+            let factor = if look_y < -0.2 { 0.085 } else { 0.06 };
+            let fluid_above = FluidState::at(map, position.x.floor() as i32, (position.y + 0.9).floor() as i32, position.z.floor() as i32).is_some();
+            if look_y <= 0.0 || input.jump || fluid_above {
+                self.velocity_y += (look_y - self.velocity_y) * factor;
+            }
+        }
+
+        let slowdown = if input.sprint { 0.9 } else { 0.8 };
+        self.accelerate(input.strafe * 0.98, input.forward * 0.98, 0.02, position.yaw);
+        let start_y = position.y;
+        self.move_entity(map, position, false);
+
+        self.velocity_x *= slowdown;
+        self.velocity_y *= 0.8;
+        self.velocity_z *= slowdown;
+        if !input.sprint {
+            self.velocity_y -= GRAVITY / 16.0;
+        }
+        self.climb_out_of_fluid(map, position, start_y);
+    }
+
+    fn travel_in_lava(&mut self, map: &Map, position: &mut PlayerPosition) {
+        let input = self.input;
+        self.accelerate(input.strafe * 0.98, input.forward * 0.98, 0.02, position.yaw);
+        let start_y = position.y;
+        self.move_entity(map, position, false);
+
+        self.velocity_x *= 0.5;
+        self.velocity_y *= if self.fluids.lava_depth <= 0.4 { 0.8 } else { 0.5 };
+        self.velocity_z *= 0.5;
+        self.velocity_y -= GRAVITY / 4.0;
+        self.climb_out_of_fluid(map, position, start_y);
+    }
+
+    /// Jumps out of the fluid when swimming against a bank that is low enough
+    fn climb_out_of_fluid(&mut self, map: &Map, position: &PlayerPosition, start_y: f64) {
+        let context = self.collision_context(position);
+        if !self.horizontal_collision {
+            return;
+        }
+        let target = Aabb::player(position.x, position.y, position.z).offset(
+            self.velocity_x,
+            self.velocity_y + 0.6 - position.y + start_y,
+            self.velocity_z,
+        );
+        if collision_boxes(map, &target, &context).is_empty() && !contains_fluid(map, &target) {
+            self.velocity_y = 0.3;
+        }
+    }
+
+    /// Sets the input to swim towards a target, for missions moving in water.
+    /// Returns the remaining distance.
+    pub fn swim_towards(&mut self, position: &mut PlayerPosition, target: (f64, f64, f64)) -> f64 {
+        let (dx, dy, dz) = (target.0 - position.x, target.1 - position.y, target.2 - position.z);
+        let horizontal_distance = (dx * dx + dz * dz).sqrt();
+        let distance = (horizontal_distance * horizontal_distance + dy * dy).sqrt();
+        if distance < 0.2 {
+            return distance;
+        }
+
+        if horizontal_distance > 0.1 {
+            position.yaw = yaw_towards(position.x, position.z, target.0, target.2);
+        }
+        position.pitch = -dy.atan2(horizontal_distance).to_degrees() as f32;
+        self.input.forward = (horizontal_distance * 4.0).min(1.0);
+        self.input.sprint = self.is_underwater();
+        if dy > 0.1 {
+            self.input.jump = true;
+        } else if dy < -0.5 && !self.swimming {
+            self.input.sneak = true;
+        }
+        distance
+    }
+
+    /// Adds the input to the velocity
+    fn accelerate(&mut self, strafe: f64, forward: f64, acceleration: f64, yaw: f32) {
+        let length = (strafe * strafe + forward * forward).sqrt();
+        if length < 1.0e-7 {
+            return;
+        }
+        let scale = acceleration / length.max(1.0);
+        let (strafe, forward) = (strafe * scale, forward * scale);
+        let yaw = (yaw as f64).to_radians();
+        let (sin, cos) = (yaw.sin(), yaw.cos());
+        self.velocity_x += strafe * cos - forward * sin;
+        self.velocity_z += forward * cos + strafe * sin;
+    }
+
+    fn collision_context(&self, position: &PlayerPosition) -> CollisionContext {
+        CollisionContext {
+            feet_y: position.y,
+            descending: self.input.sneak,
+        }
+    }
+
+    /// Applies the velocity with collisions and step up
+    fn move_entity(&mut self, map: &Map, position: &mut PlayerPosition, sneak: bool) {
+        let hitbox = Aabb::player(position.x, position.y, position.z);
+        let context = self.collision_context(position);
+        let (dx, dy, dz) = match sneak && self.on_ground {
+            true => {
+                let (dx, dz) = back_off_from_edge(map, &hitbox, &context, self.velocity_x, self.velocity_z);
+                (dx, self.velocity_y, dz)
+            }
+            false => (self.velocity_x, self.velocity_y, self.velocity_z),
+        };
+
+        let obstacles = collision_boxes(map, &hitbox.stretch(dx, dy, dz), &context);
+        let (mut mx, mut my, mut mz) = clip_movement(&obstacles, hitbox, dx, dy, dz);
+
+        // Try to climb the obstacle if it is low enough
+        let landing = self.on_ground || (dy != my && dy < 0.0);
+        if landing && (dx != mx || dz != mz) {
+            let obstacles = collision_boxes(map, &hitbox.stretch(dx, STEP_HEIGHT, dz).stretch(0.0, dy.min(0.0), 0.0), &context);
+            let (sx, sy, sz) = clip_movement(&obstacles, hitbox, dx, STEP_HEIGHT, dz);
+            let stepped = hitbox.offset(sx, sy, sz);
+            let (_, down, _) = clip_movement(&obstacles, stepped, 0.0, -sy + dy.min(0.0), 0.0);
+            if sx * sx + sz * sz > mx * mx + mz * mz {
+                mx = sx;
+                my = sy + down;
+                mz = sz;
+            }
+        }
+
+        position.x += mx;
+        position.y += my;
+        position.z += mz;
+
+        self.horizontal_collision = dx != mx || dz != mz;
+        self.on_ground = dy != my && dy < 0.0;
+        if dx != mx {
+            self.velocity_x = 0.0;
+        }
+        if dy != my {
+            self.velocity_y = 0.0;
+        }
+        if dz != mz {
+            self.velocity_z = 0.0;
+        }
+
+        let speed_factor = block_speed_factor(Physics::block_below(map, position.x, position.y + 0.5, position.z))
+            .min(block_speed_factor(Physics::block_below(map, position.x, position.y, position.z)));
+        self.velocity_x *= speed_factor;
+        self.velocity_z *= speed_factor;
+    }
+}
+
+/// Reduces a horizontal movement so that a sneaking player does not fall from the block it stands on
+fn back_off_from_edge(map: &Map, hitbox: &Aabb, context: &CollisionContext, mut dx: f64, mut dz: f64) -> (f64, f64) {
+    const STEP: f64 = 0.05;
+    let has_floor = |dx: f64, dz: f64| !collision_boxes(map, &hitbox.offset(dx, -STEP_HEIGHT, dz), context).is_empty();
+    let reduce = |value: f64| match value {
+        value if value.abs() < STEP => 0.0,
+        value if value > 0.0 => value - STEP,
+        value => value + STEP,
+    };
+
+    while dx != 0.0 && !has_floor(dx, 0.0) {
+        dx = reduce(dx);
+    }
+    while dz != 0.0 && !has_floor(0.0, dz) {
+        dz = reduce(dz);
+    }
+    while dx != 0.0 && dz != 0.0 && !has_floor(dx, dz) {
+        dx = reduce(dx);
+        dz = reduce(dz);
+    }
+    (dx, dz)
+}
+
+/// The yaw to look from a position to a target, in degrees
+pub fn yaw_towards(from_x: f64, from_z: f64, to_x: f64, to_z: f64) -> f32 {
+    (-(to_x - from_x)).atan2(to_z - from_z).to_degrees() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A stone floor at y=0 in the chunk 0 0
+    fn flat_map() -> Map {
+        let mut map = Map::new();
+        map.load_empty_chunk(0, 0);
+        for x in 0..16 {
+            for z in 0..16 {
+                map.set_block(x, 0, z, Block::Stone);
+            }
+        }
+        map
+    }
+
+    /// Walks towards +X for some ticks
+    fn walk_east(map: &Map, input: MovementInput, ticks: usize) -> (Physics, PlayerPosition) {
+        let mut physics = Physics::new();
+        let mut position = PlayerPosition {
+            x: 8.5,
+            y: 1.0,
+            z: 8.5,
+            yaw: yaw_towards(8.5, 8.5, 12.5, 8.5),
+            pitch: 0.0,
+        };
+        physics.input = input;
+        for _ in 0..ticks {
+            physics.tick(map, &mut position);
+        }
+        (physics, position)
+    }
+
+    fn forward() -> MovementInput {
+        MovementInput {
+            forward: 1.0,
+            ..MovementInput::default()
+        }
+    }
+
+    fn assert_close(value: f64, expected: f64) {
+        assert!((value - expected).abs() < 1.0e-6, "{} is not {}", value, expected);
+    }
+
+    #[test]
+    fn test_falling() {
+        let map = flat_map();
+        let mut physics = Physics::new();
+        let mut position = PlayerPosition {
+            x: 8.5,
+            y: 5.0,
+            z: 8.5,
+            yaw: 0.0,
+            pitch: 0.0,
+        };
+        for _ in 0..40 {
+            physics.tick(&map, &mut position);
+        }
+        assert_close(position.y, 1.0);
+        assert!(physics.on_ground);
+        assert_eq!((position.x, position.z), (8.5, 8.5));
+    }
+
+    #[test]
+    fn test_walls_stop_the_bot() {
+        let mut map = flat_map();
+        map.set_block(10, 1, 8, Block::Stone);
+        let (physics, position) = walk_east(&map, forward(), 40);
+        assert_close(position.x, 10.0 - PLAYER_WIDTH / 2.0);
+        assert_close(position.y, 1.0);
+        assert!(physics.horizontal_collision);
+    }
+
+    #[test]
+    fn test_step_up() {
+        let mut map = flat_map();
+        for x in 10..15 {
+            map.set_block(x, 1, 8, Block::OakSlab);
+        }
+        let (_, position) = walk_east(&map, forward(), 20);
+        assert!(position.x > 10.3);
+        assert_close(position.y, 1.5);
+    }
+
+    #[test]
+    fn test_fences_cannot_be_jumped_over() {
+        let mut map = flat_map();
+        map.set_block(10, 1, 8, Block::OakFence);
+        let input = MovementInput {
+            jump: true,
+            ..forward()
+        };
+        let (_, position) = walk_east(&map, input, 40);
+        assert!(position.x <= 10.375 - PLAYER_WIDTH / 2.0 + 1.0e-6);
+        assert_close(position.y, 1.0);
+    }
+
+    #[test]
+    fn test_low_blocks() {
+        let mut map = flat_map();
+        map.set_block(8, 1, 8, Block::WhiteCarpet);
+        map.set_block(9, 1, 9, Block::Chest);
+        for (x, z, height) in [(8.5, 8.5, 1.0625), (9.5, 9.5, 1.875), (10.5, 10.5, 1.0)] {
+            let mut physics = Physics::new();
+            let mut position = PlayerPosition { x, y: 3.0, z, yaw: 0.0, pitch: 0.0 };
+            for _ in 0..40 {
+                physics.tick(&map, &mut position);
+            }
+            assert_close(position.y, height);
+        }
+    }
+
+    #[test]
+    fn test_climbing() {
+        let mut map = flat_map();
+        for y in 1..5 {
+            map.set_block(8, y, 8, Block::Scaffolding);
+            map.set_block(10, y, 10, Block::Ladder);
+        }
+        let climb = |x: f64, y: f64, z: f64, input: MovementInput| {
+            let mut physics = Physics::new();
+            let mut position = PlayerPosition { x, y, z, yaw: 0.0, pitch: 0.0 };
+            physics.input = input;
+            for _ in 0..40 {
+                physics.tick(&map, &mut position);
+            }
+            position.y
+        };
+        let sneak = MovementInput {
+            sneak: true,
+            ..MovementInput::default()
+        };
+        let jump = MovementInput {
+            jump: true,
+            ..MovementInput::default()
+        };
+
+        // The bot stands on the scaffolding below it unless it sneaks
+        assert_close(climb(8.5, 3.0, 8.5, MovementInput::default()), 3.0);
+        assert_close(climb(8.5, 3.6, 8.5, MovementInput::default()), 3.0);
+        assert_close(climb(8.5, 3.0, 8.5, sneak), 1.0);
+        assert!(climb(8.5, 3.0, 8.5, jump) > 4.0);
+        // Ladders are the other way around
+        assert_close(climb(10.5, 3.0, 10.5, MovementInput::default()), 1.0);
+        assert_close(climb(10.5, 3.0, 10.5, sneak), 3.0);
+    }
+
+    #[test]
+    fn test_scaffolding_platform() {
+        let mut map = flat_map();
+        for x in 10..15 {
+            for z in 6..11 {
+                map.set_block(x, 0, z, Block::Scaffolding);
+            }
+        }
+        // The bot walks on the top of the platform
+        let (physics, position) = walk_east(&map, forward(), 20);
+        assert!(position.x > 11.0);
+        assert_close(position.y, 1.0);
+        assert!(physics.on_ground);
+    }
+
+    #[test]
+    fn test_sneaking_stops_at_edges() {
+        let mut map = Map::new();
+        map.load_empty_chunk(0, 0);
+        for x in 0..9 {
+            map.set_block(x, 0, 8, Block::Stone);
+        }
+        let input = MovementInput {
+            sneak: true,
+            ..forward()
+        };
+        let (physics, position) = walk_east(&map, input, 60);
+        assert!(position.x > 9.0 && position.x < 9.0 + PLAYER_WIDTH / 2.0);
+        assert_close(position.y, 1.0);
+        assert!(physics.on_ground);
+    }
+}
@@ -0,0 +1,424 @@
+use super::Aabb;
+use crate::*;
+
+/// Offsets of the default state in the state list of a block, in 1.17.1.
+/// The properties of a block are numbered like a mixed radix number, the last property varying the fastest,
+/// and the `true` values of booleans come first.
+const SLAB_DEFAULT: u32 = 3;
+const STAIRS_DEFAULT: u32 = 11;
+const FENCE_DEFAULT: u32 = 31;
+const WALL_DEFAULT: u32 = 3;
+const LADDER_DEFAULT: u32 = 1;
+
+/// Fences and walls collide higher than they look so that they cannot be jumped over
+const FENCE_HEIGHT: f64 = 1.5;
+
+fn is_slab(block: Block) -> bool {
+    matches!(
+        block,
+        Block::OakSlab
+            | Block::SpruceSlab
+            | Block::BirchSlab
+            | Block::JungleSlab
+            | Block::AcaciaSlab
+            | Block::DarkOakSlab
+            | Block::CrimsonSlab
+            | Block::WarpedSlab
+            | Block::StoneSlab
+            | Block::SmoothStoneSlab
+            | Block::SandstoneSlab
+            | Block::CutSandstoneSlab
+            | Block::PetrifiedOakSlab
+            | Block::CobblestoneSlab
+            | Block::BrickSlab
+            | Block::StoneBrickSlab
+            | Block::NetherBrickSlab
+            | Block::QuartzSlab
+            | Block::RedSandstoneSlab
+            | Block::CutRedSandstoneSlab
+            | Block::PurpurSlab
+            | Block::PrismarineSlab
+            | Block::PrismarineBrickSlab
+            | Block::DarkPrismarineSlab
+            | Block::PolishedGraniteSlab
+            | Block::SmoothRedSandstoneSlab
+            | Block::MossyStoneBrickSlab
+            | Block::PolishedDioriteSlab
+            | Block::MossyCobblestoneSlab
+            | Block::EndStoneBrickSlab
+            | Block::SmoothSandstoneSlab
+            | Block::SmoothQuartzSlab
+            | Block::GraniteSlab
+            | Block::AndesiteSlab
+            | Block::RedNetherBrickSlab
+            | Block::PolishedAndesiteSlab
+            | Block::DioriteSlab
+            | Block::BlackstoneSlab
+            | Block::PolishedBlackstoneSlab
+            | Block::PolishedBlackstoneBrickSlab
+            | Block::CutCopperSlab
+            | Block::ExposedCutCopperSlab
+            | Block::WeatheredCutCopperSlab
+            | Block::OxidizedCutCopperSlab
+            | Block::WaxedCutCopperSlab
+            | Block::WaxedExposedCutCopperSlab
+            | Block::WaxedWeatheredCutCopperSlab
+            | Block::WaxedOxidizedCutCopperSlab
+            | Block::CobbledDeepslateSlab
+            | Block::PolishedDeepslateSlab
+            | Block::DeepslateTileSlab
+            | Block::DeepslateBrickSlab
+    )
+}
+
+fn is_stairs(block: Block) -> bool {
+    matches!(
+        block,
+        Block::OakStairs
+            | Block::SpruceStairs
+            | Block::BirchStairs
+            | Block::JungleStairs
+            | Block::AcaciaStairs
+            | Block::DarkOakStairs
+            | Block::CrimsonStairs
+            | Block::WarpedStairs
+            | Block::StoneStairs
+            | Block::CobblestoneStairs
+            | Block::BrickStairs
+            | Block::StoneBrickStairs
+            | Block::NetherBrickStairs
+            | Block::SandstoneStairs
+            | Block::QuartzStairs
+            | Block::RedSandstoneStairs
+            | Block::PurpurStairs
+            | Block::PrismarineStairs
+            | Block::PrismarineBrickStairs
+            | Block::DarkPrismarineStairs
+            | Block::PolishedGraniteStairs
+            | Block::SmoothRedSandstoneStairs
+            | Block::MossyStoneBrickStairs
+            | Block::PolishedDioriteStairs
+            | Block::MossyCobblestoneStairs
+            | Block::EndStoneBrickStairs
+            | Block::SmoothSandstoneStairs
+            | Block::SmoothQuartzStairs
+            | Block::GraniteStairs
+            | Block::AndesiteStairs
+            | Block::RedNetherBrickStairs
+            | Block::PolishedAndesiteStairs
+            | Block::DioriteStairs
+            | Block::BlackstoneStairs
+            | Block::PolishedBlackstoneStairs
+            | Block::PolishedBlackstoneBrickStairs
+            | Block::CutCopperStairs
+            | Block::ExposedCutCopperStairs
+            | Block::WeatheredCutCopperStairs
+            | Block::OxidizedCutCopperStairs
+            | Block::WaxedCutCopperStairs
+            | Block::WaxedExposedCutCopperStairs
+            | Block::WaxedWeatheredCutCopperStairs
+            | Block::WaxedOxidizedCutCopperStairs
+            | Block::CobbledDeepslateStairs
+            | Block::PolishedDeepslateStairs
+            | Block::DeepslateTileStairs
+            | Block::DeepslateBrickStairs
+    )
+}
+
+fn is_fence(block: Block) -> bool {
+    matches!(
+        block,
+        Block::OakFence
+            | Block::SpruceFence
+            | Block::BirchFence
+            | Block::JungleFence
+            | Block::AcaciaFence
+            | Block::DarkOakFence
+            | Block::CrimsonFence
+            | Block::WarpedFence
+            | Block::NetherBrickFence
+    )
+}
+
+fn is_wall(block: Block) -> bool {
+    matches!(
+        block,
+        Block::CobblestoneWall
+            | Block::MossyCobblestoneWall
+            | Block::BrickWall
+            | Block::PrismarineWall
+            | Block::RedSandstoneWall
+            | Block::MossyStoneBrickWall
+            | Block::GraniteWall
+            | Block::StoneBrickWall
+            | Block::NetherBrickWall
+            | Block::AndesiteWall
+            | Block::RedNetherBrickWall
+            | Block::SandstoneWall
+            | Block::EndStoneBrickWall
+            | Block::DioriteWall
+            | Block::BlackstoneWall
+            | Block::PolishedBlackstoneWall
+            | Block::PolishedBlackstoneBrickWall
+            | Block::CobbledDeepslateWall
+            | Block::PolishedDeepslateWall
+            | Block::DeepslateTileWall
+            | Block::DeepslateBrickWall
+    )
+}
+
+fn is_carpet(block: Block) -> bool {
+    matches!(
+        block,
+        Block::WhiteCarpet
+            | Block::OrangeCarpet
+            | Block::MagentaCarpet
+            | Block::LightBlueCarpet
+            | Block::YellowCarpet
+            | Block::LimeCarpet
+            | Block::PinkCarpet
+            | Block::GrayCarpet
+            | Block::LightGrayCarpet
+            | Block::CyanCarpet
+            | Block::PurpleCarpet
+            | Block::BlueCarpet
+            | Block::BrownCarpet
+            | Block::GreenCarpet
+            | Block::RedCarpet
+            | Block::BlackCarpet
+            | Block::MossCarpet
+    )
+}
+
+/// The index of a state among the states of its block
+fn state_index(block: Block, state: u32, default_index: u32) -> u32 {
+    (state + default_index).saturating_sub(block.get_default_state_id())
+}
+
+fn cuboid(min_x: f64, min_y: f64, min_z: f64, max_x: f64, max_y: f64, max_z: f64) -> Aabb {
+    Aabb::new(min_x, min_y, min_z, max_x, max_y, max_z)
+}
+
+fn slab_boxes(index: u32) -> Vec<Aabb> {
+    // type (top, bottom, double) * waterlogged
+    match index / 2 {
+        0 => vec![cuboid(0.0, 0.5, 0.0, 1.0, 1.0, 1.0)],
+        1 => vec![cuboid(0.0, 0.0, 0.0, 1.0, 0.5, 1.0)],
+        _ => vec![cuboid(0.0, 0.0, 0.0, 1.0, 1.0, 1.0)],
+    }
+}
+
+fn stairs_boxes(index: u32) -> Vec<Aabb> {
+    // facing (north, south, west, east) * half (top, bottom) * shape (straight, inner_left, inner_right, outer_left, outer_right) * waterlogged
+    let facing = index / 20;
+    let top = (index / 10) % 2 == 0;
+    let shape = (index / 2) % 5;
+
+    // The direction the stairs go up to, and the direction on their left when facing it
+    let ((forward_x, forward_z), (left_x, left_z)) = match facing {
+        0 => ((0.0, -1.0), (-1.0, 0.0)),
+        1 => ((0.0, 1.0), (1.0, 0.0)),
+        2 => ((-1.0, 0.0), (0.0, 1.0)),
+        _ => ((1.0, 0.0), (0.0, -1.0)),
+    };
+    // Quarters of the step, as (forward, left) signs
+    let quarters: &[(f64, f64)] = match shape {
+        0 => &[(1.0, 1.0), (1.0, -1.0)],
+        1 => &[(1.0, 1.0), (1.0, -1.0), (-1.0, 1.0)],
+        2 => &[(1.0, 1.0), (1.0, -1.0), (-1.0, -1.0)],
+        3 => &[(1.0, 1.0)],
+        _ => &[(1.0, -1.0)],
+    };
+
+    let (slab_y, step_y) = match top {
+        true => (0.5, 0.0),
+        false => (0.0, 0.5),
+    };
+    let mut boxes = vec![cuboid(0.0, slab_y, 0.0, 1.0, slab_y + 0.5, 1.0)];
+    for (forward, left) in quarters {
+        let center_x = 0.5 + 0.25 * (forward_x * forward + left_x * left);
+        let center_z = 0.5 + 0.25 * (forward_z * forward + left_z * left);
+        boxes.push(cuboid(center_x - 0.25, step_y, center_z - 0.25, center_x + 0.25, step_y + 0.5, center_z + 0.25));
+    }
+    boxes
+}
+
+/// A post in the middle of the block and arms towards the connected sides (east, north, south, west)
+fn post_and_arms(post: Option<f64>, arm: f64, connections: [bool; 4]) -> Vec<Aabb> {
+    let mut boxes = Vec::new();
+    if let Some(post) = post {
+        boxes.push(cuboid(0.5 - post, 0.0, 0.5 - post, 0.5 + post, FENCE_HEIGHT, 0.5 + post));
+    }
+    let (low, high) = (0.5 - arm, 0.5 + arm);
+    let arms = [
+        cuboid(0.5, 0.0, low, 1.0, FENCE_HEIGHT, high),
+        cuboid(low, 0.0, 0.0, high, FENCE_HEIGHT, 0.5),
+        cuboid(low, 0.0, 0.5, high, FENCE_HEIGHT, 1.0),
+        cuboid(0.0, 0.0, low, 0.5, FENCE_HEIGHT, high),
+    ];
+    for (arm, connected) in arms.iter().zip(connections.iter()) {
+        if *connected {
+            boxes.push(*arm);
+        }
+    }
+    boxes
+}
+
+fn ladder_boxes(index: u32) -> Vec<Aabb> {
+    // facing (north, south, west, east) * waterlogged, the ladder is against the wall behind it
+    let thickness = 0.1875;
+    match index / 2 {
+        0 => vec![cuboid(0.0, 0.0, 1.0 - thickness, 1.0, 1.0, 1.0)],
+        1 => vec![cuboid(0.0, 0.0, 0.0, 1.0, 1.0, thickness)],
+        2 => vec![cuboid(1.0 - thickness, 0.0, 0.0, 1.0, 1.0, 1.0)],
+        _ => vec![cuboid(0.0, 0.0, 0.0, thickness, 1.0, 1.0)],
+    }
+}
+
+fn fence_boxes(index: u32) -> Vec<Aabb> {
+    // east * north * south * waterlogged * west
+    let connected = |bit: u32| (index >> bit) & 1 == 0;
+    post_and_arms(Some(0.125), 0.125, [connected(4), connected(3), connected(2), connected(0)])
+}
+
+fn wall_boxes(index: u32) -> Vec<Aabb> {
+    // east (none, low, tall) * north * south * up * waterlogged * west
+    let connected = |divisor: u32| (index / divisor) % 3 != 0;
+    let up = (index / 6) % 2 == 0;
+    post_and_arms(if up { Some(0.25) } else { None }, 0.1875, [connected(108), connected(36), connected(12), connected(1)])
+}
+
+/// The collision boxes of a block state, relative to the block origin.
+/// Unknown states have no collision, like air.
+pub fn block_collision_boxes(state: u32) -> Vec<Aabb> {
+    let block = match Block::from_state_id(state) {
+        Some(block) => block,
+        None => return Vec::new(),
+    };
+    if is_slab(block) {
+        return slab_boxes(state_index(block, state, SLAB_DEFAULT));
+    }
+    if is_stairs(block) {
+        return stairs_boxes(state_index(block, state, STAIRS_DEFAULT));
+    }
+    if is_fence(block) {
+        return fence_boxes(state_index(block, state, FENCE_DEFAULT));
+    }
+    if is_wall(block) {
+        return wall_boxes(state_index(block, state, WALL_DEFAULT));
+    }
+    if block == Block::Ladder {
+        return ladder_boxes(state_index(block, state, LADDER_DEFAULT));
+    }
+    if is_carpet(block) {
+        return vec![cuboid(0.0, 0.0, 0.0, 1.0, 0.0625, 1.0)];
+    }
+    let height = match block {
+        // The first layer has no collision, then each layer adds 2 pixels
+        Block::Snow => (state - block.get_default_state_id()) as f64 * 0.125,
+        Block::Chest | Block::TrappedChest | Block::EnderChest => return vec![cuboid(0.0625, 0.0, 0.0625, 0.9375, 0.875, 0.9375)],
+        // Depends on the entity, see [contextual_collision_boxes]
+        Block::Scaffolding => 0.0,
+        Block::SoulSand => 0.875,
+        Block::Farmland | Block::DirtPath => 0.9375,
+        block if block.is_blocking() => 1.0,
+        _ => 0.0,
+    };
+    match height > 0.0 {
+        true => vec![cuboid(0.0, 0.0, 0.0, 1.0, height, 1.0)],
+        false => Vec::new(),
+    }
+}
+
+/// What the collision of some blocks depends on, like the collision context of vanilla
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CollisionContext {
+    /// The bottom of the hitbox of the moving entity
+    pub feet_y: f64,
+    /// Whether the entity sneaks to go down
+    pub descending: bool,
+}
+
+/// Same as [block_collision_boxes] but with the shapes that depend on the moving entity.
+/// Scaffolding can be stood on from above, and entities that sneak or are inside go through it.
+pub fn contextual_collision_boxes(state: u32, block_y: i32, context: &CollisionContext) -> Vec<Aabb> {
+    if Block::from_state_id(state) != Some(Block::Scaffolding) {
+        return block_collision_boxes(state);
+    }
+    match context.feet_y > block_y as f64 + 1.0 - 1.0e-5 && !context.descending {
+        true => vec![cuboid(0.0, 0.875, 0.0, 1.0, 1.0, 1.0)],
+        false => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn top_of(boxes: &[Aabb]) -> f64 {
+        boxes.iter().map(|b| b.max_y).fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn test_slabs() {
+        let bottom = Block::OakSlab.get_default_state_id();
+        assert_eq!(block_collision_boxes(bottom), [cuboid(0.0, 0.0, 0.0, 1.0, 0.5, 1.0)]);
+        assert_eq!(block_collision_boxes(bottom - 2), [cuboid(0.0, 0.5, 0.0, 1.0, 1.0, 1.0)]);
+        assert_eq!(top_of(&block_collision_boxes(bottom + 2)), 1.0);
+    }
+
+    #[test]
+    fn test_stairs() {
+        // Facing north, bottom half, straight: a slab and a step on the north side
+        let stairs = block_collision_boxes(Block::OakStairs.get_default_state_id());
+        assert_eq!(stairs.len(), 3);
+        assert_eq!(stairs[0], cuboid(0.0, 0.0, 0.0, 1.0, 0.5, 1.0));
+        assert!(stairs[1..].iter().all(|b| b.min_z == 0.0 && b.max_z == 0.5 && b.max_y == 1.0));
+
+        // Facing east, top half, outer_left: the step is a quarter at the north east, below the slab
+        let index = 3 * 20 + 3 * 2 + 1;
+        let stairs = block_collision_boxes(Block::OakStairs.get_default_state_id() - STAIRS_DEFAULT + index);
+        assert_eq!(stairs, [cuboid(0.0, 0.5, 0.0, 1.0, 1.0, 1.0), cuboid(0.5, 0.0, 0.0, 1.0, 0.5, 0.5)]);
+    }
+
+    #[test]
+    fn test_fences_and_walls() {
+        let fence = block_collision_boxes(Block::OakFence.get_default_state_id());
+        assert_eq!(fence, [cuboid(0.375, 0.0, 0.375, 0.625, 1.5, 0.625)]);
+        // Connected to the east
+        let fence = block_collision_boxes(Block::OakFence.get_default_state_id() - 16);
+        assert_eq!(fence.len(), 2);
+        assert_eq!(fence[1].max_x, 1.0);
+
+        let wall = block_collision_boxes(Block::CobblestoneWall.get_default_state_id());
+        assert_eq!(wall, [cuboid(0.25, 0.0, 0.25, 0.75, 1.5, 0.75)]);
+        // Tall on the north and the south, without a post
+        let index = 2 * 36 + 2 * 12 + 6 + 3;
+        let wall = block_collision_boxes(Block::CobblestoneWall.get_default_state_id() - WALL_DEFAULT + index);
+        assert_eq!(wall.len(), 2);
+        assert_eq!(top_of(&wall), 1.5);
+    }
+
+    #[test]
+    fn test_low_blocks() {
+        assert_eq!(top_of(&block_collision_boxes(Block::WhiteCarpet.get_default_state_id())), 0.0625);
+        assert!(block_collision_boxes(Block::Snow.get_default_state_id()).is_empty());
+        assert_eq!(top_of(&block_collision_boxes(Block::Snow.get_default_state_id() + 4)), 0.5);
+        assert_eq!(top_of(&block_collision_boxes(Block::Chest.get_default_state_id())), 0.875);
+        assert_eq!(top_of(&block_collision_boxes(Block::Stone.get_default_state_id())), 1.0);
+        assert!(block_collision_boxes(Block::Air.get_default_state_id()).is_empty());
+        assert!(block_collision_boxes(Block::Scaffolding.get_default_state_id()).is_empty());
+        assert_eq!(block_collision_boxes(Block::Ladder.get_default_state_id()), [cuboid(0.0, 0.0, 0.8125, 1.0, 1.0, 1.0)]);
+    }
+
+    #[test]
+    fn test_scaffolding() {
+        let scaffolding = Block::Scaffolding.get_default_state_id();
+        let context = |feet_y: f64, descending: bool| CollisionContext { feet_y, descending };
+        assert_eq!(contextual_collision_boxes(scaffolding, 4, &context(5.0, false)), [cuboid(0.0, 0.875, 0.0, 1.0, 1.0, 1.0)]);
+        assert!(contextual_collision_boxes(scaffolding, 4, &context(5.0, true)).is_empty());
+        assert!(contextual_collision_boxes(scaffolding, 4, &context(4.5, false)).is_empty());
+        let stone = Block::Stone.get_default_state_id();
+        assert_eq!(contextual_collision_boxes(stone, 4, &context(0.0, true)), block_collision_boxes(stone));
+    }
+}
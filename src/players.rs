@@ -0,0 +1,203 @@
+use crate::*;
+use minecraft_protocol::components::{gamemode::Gamemode, players::PlayerInfos};
+use std::collections::HashMap;
+
+/// A player of the tab list
+#[derive(Debug, Clone)]
+pub struct PlayerInfo {
+    pub uuid: u128,
+    pub name: String,
+    pub gamemode: Gamemode,
+    /// In milliseconds, as measured by the server
+    pub latency: i32,
+    /// The name displayed in the tab list, if different from `name`
+    pub display_name: Option<ChatComponent>,
+}
+
+/// Players connected to the server, as listed in the tab list.
+/// Unlike [Entities], this includes the players that are too far away to be seen.
+#[derive(Debug, Default)]
+pub struct PlayerList {
+    players: HashMap<u128, PlayerInfo>,
+}
+
+impl PlayerList {
+    pub fn new() -> PlayerList {
+        PlayerList::default()
+    }
+
+    pub fn get(&self, uuid: u128) -> Option<&PlayerInfo> {
+        self.players.get(&uuid)
+    }
+
+    /// Finds a player by name, ignoring case like the server does
+    pub fn get_by_name(&self, name: &str) -> Option<&PlayerInfo> {
+        self.players.values().find(|player| player.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PlayerInfo> {
+        self.players.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.players.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.players.is_empty()
+    }
+
+    pub fn handle_player_info_packet(&mut self, value: PlayerInfos) {
+        match value {
+            PlayerInfos::AddPlayer(players) => {
+                for (uuid, player) in players.items {
+                    trace!("Player {} ({:032x}) added to the tab list", player.name, uuid);
+                    self.players.insert(
+                        uuid,
+                        PlayerInfo {
+                            uuid,
+                            name: player.name.to_string(),
+                            gamemode: player.gamemode,
+                            latency: player.ping.0,
+                            display_name: player.display_name.map(|name| ChatComponent::parse(name).unwrap_or_else(|_| ChatComponent::text(name))),
+                        },
+                    );
+                }
+            }
+            PlayerInfos::UpdateGamemode(players) => {
+                for (uuid, gamemode) in players.items {
+                    match self.players.get_mut(&uuid) {
+                        Some(player) => player.gamemode = gamemode,
+                        None => warn!("Gamemode update for unknown player {:032x}", uuid),
+                    }
+                }
+            }
+            PlayerInfos::UpdateLatency(players) => {
+                for (uuid, latency) in players.items {
+                    if let Some(player) = self.players.get_mut(&uuid) {
+                        player.latency = latency.0;
+                    }
+                }
+            }
+            PlayerInfos::UpdateDisplayName(players) => {
+                for (uuid, display_name) in players.items {
+                    if let Some(player) = self.players.get_mut(&uuid) {
+                        player.display_name = display_name.map(|name| ChatComponent::parse(name).unwrap_or_else(|_| ChatComponent::text(name)));
+                    }
+                }
+            }
+            PlayerInfos::RemovePlayer(uuids) => {
+                for uuid in uuids.items {
+                    if let Some(player) = self.players.remove(&uuid) {
+                        trace!("Player {} ({:032x}) removed from the tab list", player.name, uuid);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use minecraft_protocol::MinecraftPacketPart;
+
+    const ALICE: u128 = 1;
+    const BOB: u128 = 2;
+    const UNKNOWN: u128 = 3;
+
+    fn string(value: &str) -> Vec<u8> {
+        let mut bytes = vec![value.len() as u8];
+        bytes.extend_from_slice(value.as_bytes());
+        bytes
+    }
+
+    /// Builds a player info packet of protocol 756 and feeds it to the list
+    fn handle(list: &mut PlayerList, action: u8, entries: Vec<(u128, Vec<u8>)>) {
+        let mut packet = vec![0x36, action, entries.len() as u8];
+        for (uuid, data) in entries {
+            packet.extend_from_slice(&uuid.to_be_bytes());
+            packet.extend(data);
+        }
+        match ClientboundPacket::deserialize_uncompressed_minecraft_packet(packet.as_mut_slice()) {
+            Ok(ClientboundPacket::PlayerInfo { value }) => list.handle_player_info_packet(value),
+            Ok(_) => panic!("Not a player info packet"),
+            Err(e) => panic!("Invalid player info packet: {}", e),
+        }
+    }
+
+    /// No properties, then the gamemode, the latency and the display name
+    fn add(name: &str, gamemode: u8, latency: u8, display_name: Option<&str>) -> Vec<u8> {
+        let mut data = string(name);
+        data.extend_from_slice(&[0, gamemode, latency]);
+        data.extend(optional_name(display_name));
+        data
+    }
+
+    fn optional_name(display_name: Option<&str>) -> Vec<u8> {
+        match display_name {
+            Some(display_name) => [vec![1], string(display_name)].concat(),
+            None => vec![0],
+        }
+    }
+
+    fn with_players() -> PlayerList {
+        let mut list = PlayerList::new();
+        handle(
+            &mut list,
+            0,
+            vec![(ALICE, add("Alice", 0, 20, Some(r#"{"text":"Queen Alice"}"#))), (BOB, add("Bob", 1, 80, None))],
+        );
+        list
+    }
+
+    #[test]
+    fn test_add() {
+        let list = with_players();
+        assert_eq!(list.len(), 2);
+        let alice = list.get(ALICE).unwrap();
+        assert_eq!(alice.name, "Alice");
+        assert_eq!(alice.latency, 20);
+        assert!(matches!(alice.gamemode, Gamemode::Survival));
+        assert_eq!(alice.display_name.as_ref().unwrap().to_plain(), "Queen Alice");
+        let bob = list.get_by_name("bOB").unwrap();
+        assert_eq!(bob.uuid, BOB);
+        assert!(matches!(bob.gamemode, Gamemode::Creative));
+        assert!(bob.display_name.is_none());
+    }
+
+    #[test]
+    fn test_updates() {
+        let mut list = with_players();
+        handle(&mut list, 1, vec![(ALICE, vec![1]), (UNKNOWN, vec![1])]);
+        handle(&mut list, 2, vec![(BOB, vec![42]), (UNKNOWN, vec![42])]);
+        handle(
+            &mut list,
+            3,
+            vec![(ALICE, optional_name(None)), (BOB, optional_name(Some(r#"{"text":"Bobby"}"#))), (UNKNOWN, optional_name(None))],
+        );
+
+        // Updates of players that are not listed are ignored
+        assert_eq!(list.len(), 2);
+        assert!(list.get(UNKNOWN).is_none());
+
+        let alice = list.get(ALICE).unwrap();
+        assert!(matches!(alice.gamemode, Gamemode::Creative));
+        assert_eq!(alice.latency, 20);
+        assert!(alice.display_name.is_none());
+        let bob = list.get(BOB).unwrap();
+        assert_eq!(bob.latency, 42);
+        assert_eq!(bob.display_name.as_ref().unwrap().to_plain(), "Bobby");
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut list = with_players();
+        handle(&mut list, 4, vec![(ALICE, Vec::new()), (UNKNOWN, Vec::new())]);
+        assert_eq!(list.len(), 1);
+        assert!(list.get(ALICE).is_none());
+        assert!(list.get_by_name("Bob").is_some());
+        handle(&mut list, 4, vec![(BOB, Vec::new())]);
+        assert!(list.is_empty());
+    }
+}
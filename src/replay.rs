@@ -0,0 +1,78 @@
+use crate::network::{PacketDirection, PacketRecording, PacketSender};
+use crate::*;
+use minecraft_protocol::MinecraftPacketPart;
+use std::{path::Path, time::Duration};
+
+const TICK_DURATION: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    /// Number of clientbound packets fed into [Bot::update]
+    pub clientbound_packets: usize,
+    /// Number of serverbound packets found in the recording (they are not replayed)
+    pub recorded_serverbound_packets: usize,
+    /// Number of clientbound packets that could not be parsed
+    pub parse_failures: usize,
+    /// Number of times [Bot::act] was called
+    pub ticks: usize,
+    /// Every packet the replayed bot wanted to send
+    pub sent_packets: Vec<Vec<u8>>,
+}
+
+/// Feeds a recording made by a [network::PacketRecorder] into a fresh bot, without any socket.
+/// [Bot::act] is called once every 50ms of recorded time so that missions run like they did live.
+/// The bot is returned along with a report so that its final state can be inspected.
+pub fn replay(path: impl AsRef<Path>, username: String) -> Result<(Bot, ReplayReport), String> {
+    let recording = PacketRecording::open(path)?;
+    let (sender, receiver) = PacketSender::channel();
+    let mut bot = Bot::new(BotConfig::new("replay", 0, username), sender);
+    let mut report = ReplayReport::default();
+    let mut next_tick = TICK_DURATION;
+
+    for recorded_packet in recording {
+        if recorded_packet.direction == PacketDirection::Serverbound {
+            report.recorded_serverbound_packets += 1;
+            continue;
+        }
+
+        while next_tick <= recorded_packet.time {
+            let response_packets = bot.act();
+            report.ticks += 1;
+            next_tick += TICK_DURATION;
+            collect_packets(response_packets, &mut report);
+        }
+
+        let mut packet_bytes = recorded_packet.data;
+        let packet = match ClientboundPacket::deserialize_uncompressed_minecraft_packet(packet_bytes.as_mut_slice()) {
+            Ok(packet) => packet,
+            Err(e) => {
+                error!("Failed to parse recorded clientbound packet: {:?}. In {:?}", e, packet_bytes);
+                report.parse_failures += 1;
+                continue;
+            }
+        };
+        report.clientbound_packets += 1;
+        let response_packets = bot.update(packet);
+        collect_packets(response_packets, &mut report);
+
+        // Packets sent directly by the inventory or the entities
+        report.sent_packets.extend(receiver.try_iter().map(|(_, packet)| packet));
+    }
+    report.sent_packets.extend(receiver.try_iter().map(|(_, packet)| packet));
+
+    info!(
+        "Replayed {} packets in {} ticks ({} parse failures)",
+        report.clientbound_packets, report.ticks, report.parse_failures
+    );
+
+    Ok((bot, report))
+}
+
+fn collect_packets(packets: Vec<ServerboundPacket>, report: &mut ReplayReport) {
+    for packet in packets {
+        match packet.serialize_minecraft_packet() {
+            Ok(packet) => report.sent_packets.push(packet),
+            Err(e) => error!("Failed to serialize packet from client {}", e),
+        }
+    }
+}
@@ -0,0 +1,135 @@
+/// Ticks in a Minecraft day
+pub const DAY_LENGTH: i64 = 24000;
+
+/// The time of the world, updated by the server every second and by the bot at every tick in between
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WorldTime {
+    /// Ticks since the world was created
+    pub world_age: i64,
+    /// Ticks since the first morning. Negative if the daylight cycle is stopped.
+    pub time_of_day: i64,
+}
+
+impl WorldTime {
+    /// Advances the time by one tick, like the vanilla client between updates
+    pub(crate) fn tick(&mut self) {
+        self.world_age += 1;
+        if self.time_of_day >= 0 {
+            self.time_of_day += 1;
+        }
+    }
+
+    pub fn is_daylight_cycle_enabled(&self) -> bool {
+        self.time_of_day >= 0
+    }
+
+    /// The time within the current day, from 0 (sunrise) to 23999
+    pub fn day_time(&self) -> i64 {
+        self.time_of_day.abs() % DAY_LENGTH
+    }
+
+    /// How many days passed since the first morning
+    pub fn day(&self) -> i64 {
+        self.time_of_day.abs() / DAY_LENGTH
+    }
+
+    /// Whether it is dark enough for hostile mobs to spawn in the open
+    pub fn is_night(&self) -> bool {
+        (13000..23000).contains(&self.day_time())
+    }
+
+    /// Whether players can sleep in a bed, when the weather is clear
+    pub fn is_sleep_time(&self) -> bool {
+        (12542..23460).contains(&self.day_time())
+    }
+}
+
+/// The weather of the world, as told by the ChangeGameState packets
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Weather {
+    /// Whether the server announced rain. The levels then fade in or out over several ticks.
+    pub rain_expected: bool,
+    /// From 0 to 1, increases progressively when rain starts
+    pub rain_level: f32,
+    /// From 0 to 1, increases progressively when a thunderstorm starts
+    pub thunder_level: f32,
+}
+
+impl Weather {
+    /// Thresholds used by the vanilla client
+    pub fn is_raining(&self) -> bool {
+        self.rain_level > 0.2
+    }
+
+    pub fn is_thundering(&self) -> bool {
+        self.is_raining() && self.thunder_level > 0.9
+    }
+
+    /// Applies a ChangeGameState packet.
+    /// Returns true if [Weather::is_raining] or [Weather::is_thundering] changed, which only the levels decide.
+    pub(crate) fn handle_game_state(&mut self, reason: u8, value: f32) -> bool {
+        let (was_raining, was_thundering) = (self.is_raining(), self.is_thundering());
+        match reason {
+            // The names of the reasons 1 and 2 are swapped in the protocol
+            1 => self.rain_expected = true,
+            2 => self.rain_expected = false,
+            7 => self.rain_level = value.max(0.0).min(1.0),
+            8 => self.thunder_level = value.max(0.0).min(1.0),
+            _ => (),
+        }
+        self.is_raining() != was_raining || self.is_thundering() != was_thundering
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time() {
+        let mut time = WorldTime {
+            world_age: 100,
+            time_of_day: DAY_LENGTH * 3 + 12999,
+        };
+        assert_eq!(time.day(), 3);
+        assert!(!time.is_night());
+        assert!(time.is_sleep_time());
+        time.tick();
+        assert_eq!((time.world_age, time.day_time()), (101, 13000));
+        assert!(time.is_night());
+
+        // The daylight cycle is stopped
+        let mut time = WorldTime {
+            world_age: 0,
+            time_of_day: -6000,
+        };
+        time.tick();
+        assert_eq!((time.world_age, time.day_time()), (1, 6000));
+        assert!(!time.is_daylight_cycle_enabled());
+    }
+
+    #[test]
+    fn test_weather() {
+        let mut weather = Weather::default();
+        // Rain is announced, then fades in
+        assert!(!weather.handle_game_state(1, 0.0));
+        assert!(weather.rain_expected && !weather.is_raining());
+        assert!(!weather.handle_game_state(7, 0.1));
+        assert!(weather.handle_game_state(7, 0.5));
+        assert!(!weather.handle_game_state(7, 1.0));
+        assert!(weather.handle_game_state(8, 1.0));
+        assert!(weather.is_thundering());
+
+        // The end of the rain does not cut the levels, they fade out
+        assert!(!weather.handle_game_state(2, 0.0));
+        assert!(!weather.rain_expected && weather.is_raining());
+        assert!(!weather.handle_game_state(7, 0.9));
+        assert!(weather.handle_game_state(7, 0.1));
+        assert!(!weather.is_raining() && !weather.is_thundering());
+        assert!(!weather.handle_game_state(7, 0.0));
+
+        // Out of range levels are clamped
+        weather.handle_game_state(7, 5.0);
+        assert_eq!(weather.rain_level, 1.0);
+    }
+}